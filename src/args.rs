@@ -0,0 +1,311 @@
+use std::env;
+
+use crate::trace::Tracer;
+use crate::symbols::Symbols;
+use crate::profiler::Profiler;
+use crate::viewers::Palette;
+use crate::cheats::Cheats;
+use crate::gameboy::GameBoy;
+use crate::link::{self, Cable};
+use crate::printer::Printer;
+use crate::capture::Recorder;
+use crate::dump::Dumper;
+
+/// everything that can be passed in from the command line.
+///
+/// the first argument that isnt a flag is always the rom path
+pub struct Args {
+    pub rom_path: String,
+    // run without a window, only useful alongside the flags below
+    pub headless: bool,
+    // run under the command line debugger instead
+    pub debug: bool,
+    // serve a gdb remote stub on this port instead
+    pub gdb: Option<u16>,
+    pub frames: u32,
+    // golden image testing
+    pub reference: Option<String>,
+    pub diff: Option<String>,
+    pub hash: Option<u64>,
+    // how much memory the rewind buffer can use and how often it snapshots
+    pub rewind_mb: usize,
+    pub rewind_interval: u32,
+    // input movies, recorded from the window and played back headless
+    pub record: Option<String>,
+    pub movie: Option<String>,
+    // how many times faster holding tab runs, 0 runs as fast as possible
+    pub ff_speed: u32,
+    // instruction tracing, the filters only apply when there is a file
+    pub trace: Option<String>,
+    pub trace_pc: Option<(u16, u16)>,
+    pub trace_bank: Option<u8>,
+    pub trace_limit: Option<u64>,
+    // labels for the debugger and traces, found next to the rom if not given
+    pub sym: Option<String>,
+    pub trace_symbols: bool,
+    // LY always reads 0x90, the way gameboy doctor's reference logs were made
+    pub trace_doctor: bool,
+    // where the profiler's report and folded stacks go, it only runs with one of them
+    pub profile: Option<String>,
+    pub profile_folded: Option<String>,
+    // the vram viewers are written here once a headless run finishes
+    pub dump_vram: Option<String>,
+    pub tile_palette: Palette,
+    // game genie and gameshark codes, found next to the rom if not given
+    pub cheats: Option<String>,
+    // ips, ups or bps patches applied to the rom when it is loaded
+    pub patches: Vec<String>,
+    // let the cpu at vram and oam while the ppu is using them
+    pub lax_access: bool,
+    // a second gameboy in this process on the other end of the link cable,
+    // or another emulator to wait for or plug into
+    pub link_rom: Option<String>,
+    pub link_host: Option<String>,
+    pub link_join: Option<String>,
+    // a game boy printer on the link cable, printing into this directory
+    pub printer: Option<String>,
+    // the last headless frame as a png, and a gif or apng of a range of
+    // frames (all of them by default). both are blown up by `scale`
+    pub screenshot: Option<String>,
+    pub video: Option<String>,
+    pub video_frames: Option<(u32, u32)>,
+    pub scale: u32,
+    // every frame and its audio uncompressed for encoding afterwards, see `Dumper`
+    pub dump_video: Option<String>,
+    pub dump_audio: Option<String>,
+    pub dump_timestamps: Option<String>,
+    pub audio_rate: u32,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let mut args = env::args().skip(1);
+        let mut rom_path = None;
+        let mut parsed = Self {
+            rom_path: String::new(),
+            headless: false,
+            debug: false,
+            gdb: None,
+            frames: 60,
+            reference: None,
+            diff: None,
+            hash: None,
+            rewind_mb: 32,
+            rewind_interval: 4,
+            record: None,
+            movie: None,
+            ff_speed: 4,
+            trace: None,
+            trace_pc: None,
+            trace_bank: None,
+            trace_limit: None,
+            sym: None,
+            trace_symbols: false,
+            trace_doctor: false,
+            profile: None,
+            profile_folded: None,
+            dump_vram: None,
+            tile_palette: Palette::Bgp,
+            cheats: None,
+            patches: Vec::new(),
+            lax_access: false,
+            link_rom: None,
+            link_host: None,
+            link_join: None,
+            printer: None,
+            screenshot: None,
+            video: None,
+            video_frames: None,
+            scale: 1,
+            dump_video: None,
+            dump_audio: None,
+            dump_timestamps: None,
+            audio_rate: 48000,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => parsed.headless = true,
+                "--debug" => parsed.debug = true,
+                "--gdb" => parsed.gdb = Some(parse_number(&arg, args.next())),
+                "--frames" => parsed.frames = parse_number(&arg, args.next()),
+                "--reference" => parsed.reference = Some(value(&arg, args.next())),
+                "--diff" => parsed.diff = Some(value(&arg, args.next())),
+                "--hash" => {
+                    let hash = value(&arg, args.next());
+                    match u64::from_str_radix(hash.trim_start_matches("0x"), 16) {
+                        Ok(h) => parsed.hash = Some(h),
+                        Err(_) => panic!("invalid hash provided: {hash}"),
+                    }
+                }
+                "--rewind-mb" => parsed.rewind_mb = parse_number(&arg, args.next()),
+                "--rewind-interval" => parsed.rewind_interval = parse_number(&arg, args.next()),
+                "--record" => parsed.record = Some(value(&arg, args.next())),
+                "--movie" => parsed.movie = Some(value(&arg, args.next())),
+                "--ff-speed" => parsed.ff_speed = parse_number(&arg, args.next()),
+                "--trace" => parsed.trace = Some(value(&arg, args.next())),
+                "--trace-pc" => {
+                    let range = value(&arg, args.next());
+                    let parse = |s: &str| match u16::from_str_radix(s.trim_start_matches('$').trim_start_matches("0x"), 16) {
+                        Err(_) => panic!("invalid address in {range}"),
+                        Ok(a) => a,
+                    };
+                    parsed.trace_pc = match range.split_once('-') {
+                        Some((start, end)) => Some((parse(start), parse(end))),
+                        None => panic!("--trace-pc expects a range like 0150-01FF"),
+                    };
+                }
+                "--trace-bank" => parsed.trace_bank = Some(parse_number(&arg, args.next())),
+                "--trace-limit" => parsed.trace_limit = Some(parse_number(&arg, args.next())),
+                "--sym" => parsed.sym = Some(value(&arg, args.next())),
+                "--trace-symbols" => parsed.trace_symbols = true,
+                "--trace-doctor" => parsed.trace_doctor = true,
+                "--profile" => parsed.profile = Some(value(&arg, args.next())),
+                "--profile-folded" => parsed.profile_folded = Some(value(&arg, args.next())),
+                "--patch" => parsed.patches.push(value(&arg, args.next())),
+                "--lax-access" => parsed.lax_access = true,
+                "--link-rom" => parsed.link_rom = Some(value(&arg, args.next())),
+                "--link-host" => parsed.link_host = Some(value(&arg, args.next())),
+                "--link-join" => parsed.link_join = Some(value(&arg, args.next())),
+                "--printer" => parsed.printer = Some(value(&arg, args.next())),
+                "--screenshot" => parsed.screenshot = Some(value(&arg, args.next())),
+                "--video" => parsed.video = Some(value(&arg, args.next())),
+                "--video-frames" => {
+                    let range = value(&arg, args.next());
+                    let parse = |s: &str| match s.parse() {
+                        Err(_) => panic!("invalid frame number in {range}"),
+                        Ok(n) => n,
+                    };
+                    parsed.video_frames = match range.split_once('-') {
+                        Some((start, end)) => Some((parse(start), parse(end))),
+                        None => panic!("--video-frames expects a range like 60-300"),
+                    };
+                }
+                "--scale" => parsed.scale = parse_number(&arg, args.next()),
+                "--dump-video" => parsed.dump_video = Some(value(&arg, args.next())),
+                "--dump-audio" => parsed.dump_audio = Some(value(&arg, args.next())),
+                "--dump-timestamps" => parsed.dump_timestamps = Some(value(&arg, args.next())),
+                "--audio-rate" => parsed.audio_rate = parse_number(&arg, args.next()),
+                "--cheats" => parsed.cheats = Some(value(&arg, args.next())),
+                "--dump-vram" => parsed.dump_vram = Some(value(&arg, args.next())),
+                "--tile-palette" => {
+                    let name = value(&arg, args.next());
+                    parsed.tile_palette = match Palette::parse(&name) {
+                        None => panic!("unknown palette {name}, try bgp, obp0, obp1 or raw"),
+                        Some(p) => p,
+                    };
+                }
+                _ if arg.starts_with("--") => panic!("unknown flag: {arg}"),
+                _ => rom_path = Some(arg),
+            }
+        }
+        match rom_path {
+            // no file path provided
+            None => panic!("no file path was provided"),
+            Some(path) => parsed.rom_path = path,
+        }
+        parsed
+    }
+
+    pub fn tracer(&self) -> Option<Tracer> {
+        let path = self.trace.as_ref()?;
+        // labels are left out by default since they break diffing against other emulators
+        let symbols = if self.trace_symbols { Some(self.symbols()) } else { None };
+        Some(Tracer::new(path, self.trace_pc, self.trace_bank, self.trace_limit, symbols))
+    }
+
+    pub fn profiler(&self) -> Option<Profiler> {
+        if self.profile.is_none() && self.profile_folded.is_none() {
+            return None;
+        }
+        Some(Profiler::new(self.profile.clone(), self.profile_folded.clone(), self.symbols()))
+    }
+
+    pub fn rom(&self) -> Vec<u8> {
+        crate::get_patched_rom(&self.rom_path, &self.patches)
+    }
+
+    pub fn cheats(&self) -> Cheats {
+        match self.load_cheats() {
+            Err(e) => panic!("{e}"),
+            Ok(c) => c,
+        }
+    }
+
+    /// the cheats from `--cheats`, or the file next to the rom
+    pub fn load_cheats(&self) -> Result<Cheats, String> {
+        match &self.cheats {
+            None => Cheats::for_rom(&self.rom_path),
+            Some(path) => Cheats::load(path),
+        }
+    }
+
+    /// the cable to another emulator, which waits for it to connect when
+    /// hosting, or to the printer
+    pub fn cable(&self) -> Option<Box<dyn Cable>> {
+        let cable = match (&self.link_host, &self.link_join, &self.printer) {
+            (Some(address), _, _) => link::host(address),
+            (_, Some(address), _) => link::join(address),
+            (_, _, Some(dir)) => Ok(Box::new(Printer::new(dir)) as Box<dyn Cable>),
+            _ => return None,
+        };
+        match cable {
+            Err(e) => panic!("{e}"),
+            Ok(c) => Some(c),
+        }
+    }
+
+    pub fn recorder(&self) -> Option<Recorder> {
+        let path = self.video.as_ref()?;
+        match Recorder::new(path, self.scale) {
+            Err(e) => panic!("{e}"),
+            Ok(r) => Some(r),
+        }
+    }
+
+    pub fn dumper(&self) -> Option<Dumper> {
+        if self.dump_video.is_none() && self.dump_audio.is_none() && self.dump_timestamps.is_none() {
+            return None;
+        }
+        let dumper = Dumper::new(
+            self.dump_video.as_deref(),
+            self.dump_audio.as_deref(),
+            self.dump_timestamps.as_deref(),
+            self.audio_rate,
+        );
+        match dumper {
+            Err(e) => panic!("{e}"),
+            Ok(d) => Some(d),
+        }
+    }
+
+    /// the gameboy on the other end of the cable when both are in this process
+    pub fn link_gameboy(&self) -> Option<GameBoy> {
+        let path = self.link_rom.as_ref()?;
+        Some(GameBoy::new(crate::get_rom(path)))
+    }
+
+    pub fn symbols(&self) -> Symbols {
+        match &self.sym {
+            None => Symbols::for_rom(&self.rom_path),
+            Some(path) => match Symbols::load(path) {
+                Err(e) => panic!("{e}"),
+                Ok(s) => s,
+            },
+        }
+    }
+}
+
+fn value(flag: &str, next: Option<String>) -> String {
+    match next {
+        None => panic!("{flag} expects a value"),
+        Some(v) => v,
+    }
+}
+fn parse_number<T: std::str::FromStr>(flag: &str, next: Option<String>) -> T {
+    let v = value(flag, next);
+    match v.parse() {
+        Err(_) => panic!("{flag} expects a number, got {v}"),
+        Ok(n) => n,
+    }
+}
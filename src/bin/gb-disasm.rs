@@ -0,0 +1,68 @@
+use std::env;
+
+use gameboy_emulator::disasm::{disassemble_rom, rom_location};
+use gameboy_emulator::symbols::Symbols;
+use gameboy_emulator::get_rom;
+
+/// prints a rom as RGBDS assembly
+///
+/// usage: gb-disasm <rom> [start[-end]]
+///
+/// the range is given as hexadecimal offsets into the rom file and
+/// defaults to the whole thing. every bank gets its own section.
+/// labels come from the rom's `.sym` file when there is one next to it
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        panic!("usage: gb-disasm <rom> [start[-end]]");
+    }
+    let rom = get_rom(&args[1]);
+
+    let (start, end) = match args.get(2) {
+        None => (0, rom.len()),
+        Some(range) => {
+            let parse = |s: &str| match usize::from_str_radix(s.trim_start_matches('$').trim_start_matches("0x"), 16) {
+                Err(_) => panic!("invalid offset {s}"),
+                Ok(n) => n,
+            };
+            match range.split_once('-') {
+                Some((start, end)) => (parse(start), parse(end)),
+                None => (parse(range), rom.len()),
+            }
+        }
+    };
+
+    let symbols = Symbols::for_rom(&args[1]);
+
+    println!("; disassembled from {}", args[1]);
+    // labels outside the rom arent placed by any section, so they are defined up front
+    let outside: Vec<_> = symbols.iter().filter(|(_, address, _)| *address >= 0x8000).collect();
+    if !outside.is_empty() {
+        println!();
+    }
+    for (_, address, name) in outside {
+        println!("DEF {name} EQU ${address:04X}");
+    }
+    let mut offset = start;
+    while offset < end.min(rom.len()) {
+        let (bank, address) = rom_location(offset);
+        println!();
+        if bank == 0 {
+            println!("SECTION \"ROM Bank $000\", ROM0[${address:04X}]");
+        } else {
+            println!("SECTION \"ROM Bank ${bank:03X}\", ROMX[${address:04X}], BANK[${bank:X}]");
+        }
+
+        // each call stops at the end of the bank
+        let instructions = disassemble_rom(&rom, offset, end);
+        for instruction in &instructions {
+            if let Some(label) = symbols.label_at(bank as u8, instruction.address) {
+                println!("{label}:");
+            }
+            let text = instruction.text_with_symbols(&symbols, bank as u8);
+            let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{b:02X}")).collect();
+            println!("    {text:<24}; ${:04X}: {}", instruction.address, bytes.join(" "));
+            offset += instruction.bytes.len();
+        }
+    }
+}
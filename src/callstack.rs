@@ -0,0 +1,61 @@
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+
+/// a function that has been called and not returned from yet
+#[derive(Clone, Copy)]
+pub struct Frame {
+    // where the call went to, and the rom bank that was in
+    pub function: u16,
+    pub bank: u8,
+    pub return_address: u16,
+    // where the return address sits on the stack
+    pub sp: u16,
+}
+
+/// follows the calls and returns the cpu makes so the debugger can show
+/// how it got somewhere. it only watches the stack pointer and pc around
+/// each step, so calls, rsts and interrupts are all treated the same
+#[derive(Default)]
+pub struct CallStack {
+    pub frames: Vec<Frame>,
+    // the cpu state from before the step
+    pc: u16,
+    sp: u16,
+    opcode: u8,
+}
+
+impl CallStack {
+    /// has to be called before every step
+    pub fn before_step(&mut self, cpu: &Cpu, memory: &Memory) {
+        self.pc = cpu.regs.pc;
+        self.sp = cpu.regs.sp;
+        self.opcode = memory.peek(self.pc);
+    }
+
+    /// and this after it. returns the frame that was just returned from, if any
+    pub fn after_step(&mut self, cpu: &Cpu, memory: &Memory) -> Option<Frame> {
+        let pc = cpu.regs.pc;
+        let sp = cpu.regs.sp;
+        // a push also moves sp down by 2, but it doesnt go anywhere
+        let jumped = pc != self.pc.wrapping_add(crate::disasm::instruction_length(self.opcode));
+        if sp == self.sp.wrapping_sub(2) && jumped {
+            let return_address = crate::combine_u8s(memory.peek(sp), memory.peek(sp.wrapping_add(1)));
+            self.frames.push(Frame { function: pc, bank: memory.rom_bank(pc), return_address, sp });
+            return None;
+        }
+
+        let returned = matches!(self.opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9);
+        if returned && sp == self.sp.wrapping_add(2) {
+            // anything which was left behind on the stack is gone too
+            let mut last = None;
+            while let Some(frame) = self.frames.last() {
+                if frame.sp >= sp {
+                    break;
+                }
+                last = self.frames.pop();
+            }
+            return last;
+        }
+        None
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs::File;
+use std::io::BufWriter;
+
+use crate::framebuffer::Framebuffer;
+use crate::gif::Gif;
+use crate::pacing::{CYCLES_PER_FRAME, CLOCK_SPEED};
+use crate::png::Apng;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Format {
+    Gif,
+    Apng,
+}
+
+impl Format {
+    fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "gif" => Some(Self::Gif),
+            "png" | "apng" => Some(Self::Apng),
+            _ => None,
+        }
+    }
+    /// delays are in hundredths of a second for gifs and milliseconds for pngs
+    fn units_per_second(self) -> u64 {
+        match self {
+            Self::Gif => 100,
+            Self::Apng => 1000,
+        }
+    }
+    /// most viewers slow gif frames shorter than 2 hundredths of a second right
+    /// down, so at 59.73 fps roughly every other frame has to be dropped
+    fn shortest_delay(self) -> u64 {
+        match self {
+            Self::Gif => 2,
+            Self::Apng => 1,
+        }
+    }
+}
+
+enum Encoder {
+    Gif(Gif<BufWriter<File>>),
+    Apng(Apng<BufWriter<File>>),
+}
+
+/// records frames as an animated gif or png, going by the file's extension.
+///
+/// frames are timed by the gameboy's clock rather than the wall clock, so
+/// the recording plays back at the real 59.73 fps even if it was made
+/// while fast forwarding. a frame is only written once a different one
+/// comes along, as only then is it known how long it stayed on screen
+pub struct Recorder {
+    path: String,
+    format: Format,
+    scale: u32,
+    // made once there is a frame to write, as that decides the size
+    encoder: Option<Encoder>,
+    // the frame on screen and the number of the frame it first appeared on
+    pending: Option<(Framebuffer, u32)>,
+    frames: u32,
+}
+
+impl Recorder {
+    pub fn new(path: &str, scale: u32) -> Result<Self, String> {
+        let Some(format) = Format::from_path(path) else {
+            return Err(format!("cannot record to {path}, it should end in .gif, .png or .apng"));
+        };
+        Ok(Self { path: path.to_string(), format, scale, encoder: None, pending: None, frames: 0 })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn frame(&mut self, frame: &Framebuffer) -> Result<(), String> {
+        let number = self.frames;
+        self.frames += 1;
+        let Some((pending, start)) = &self.pending else {
+            self.pending = Some((frame.clone(), number));
+            return Ok(());
+        };
+        if pending == frame {
+            return Ok(());
+        }
+        let start = *start;
+        // a frame too short to show is replaced, the next one just comes in a little early
+        if self.delay(start, number) < self.format.shortest_delay() {
+            self.pending = Some((frame.clone(), start));
+            return Ok(());
+        }
+        self.write(start, number)?;
+        self.pending = Some((frame.clone(), number));
+        Ok(())
+    }
+
+    /// writes the last frame out and returns how many frames were recorded
+    pub fn finish(mut self) -> Result<u32, String> {
+        if let Some(start) = self.pending.as_ref().map(|(_, start)| *start) {
+            self.write(start, self.frames)?;
+        }
+        let finished = match self.encoder {
+            None => return Ok(0),
+            Some(Encoder::Gif(gif)) => gif.finish().map(|_| ()),
+            Some(Encoder::Apng(apng)) => apng.finish().map(|_| ()),
+        };
+        finished.map_err(|e| format!("could not write {}: {e}", self.path))?;
+        Ok(self.frames)
+    }
+
+    /// how long from the start of one frame to the start of another, in the format's units
+    fn delay(&self, start: u32, end: u32) -> u64 {
+        let time = |frame: u32| {
+            let units = frame as u64 * CYCLES_PER_FRAME as u64 * self.format.units_per_second();
+            (units + CLOCK_SPEED as u64 / 2) / CLOCK_SPEED as u64
+        };
+        time(end) - time(start)
+    }
+
+    fn write(&mut self, start: u32, end: u32) -> Result<(), String> {
+        let delay = self.delay(start, end).min(u16::MAX as u64) as u16;
+        let Some((frame, _)) = &self.pending else {
+            return Ok(());
+        };
+        let (width, height, rgba) = frame.scaled_rgba(self.scale);
+        let path = self.path.clone();
+        let error = |e: std::io::Error| format!("could not write {path}: {e}");
+        if self.encoder.is_none() {
+            let file = BufWriter::new(File::create(&self.path).map_err(error)?);
+            self.encoder = Some(match self.format {
+                Format::Gif => Encoder::Gif(Gif::new(file, width as u16, height as u16).map_err(error)?),
+                Format::Apng => Encoder::Apng(Apng::new(file, width, height).map_err(error)?),
+            });
+        }
+        let written = match self.encoder.as_mut().unwrap() {
+            Encoder::Gif(gif) => gif.frame(&rgba, delay),
+            Encoder::Apng(apng) => apng.frame(&rgba, delay),
+        };
+        written.map_err(error)
+    }
+}
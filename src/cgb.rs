@@ -0,0 +1,168 @@
+use crate::savestate::{StateWriter, StateReader};
+
+pub const KEY1: u16 = 0xFF4D;
+pub const VBK: u16 = 0xFF4F;
+pub const HDMA1: u16 = 0xFF51;
+pub const HDMA2: u16 = 0xFF52;
+pub const HDMA3: u16 = 0xFF53;
+pub const HDMA4: u16 = 0xFF54;
+pub const HDMA5: u16 = 0xFF55;
+pub const BCPS: u16 = 0xFF68;
+pub const BCPD: u16 = 0xFF69;
+pub const OCPS: u16 = 0xFF6A;
+pub const OCPD: u16 = 0xFF6B;
+pub const SVBK: u16 = 0xFF70;
+
+/// header byte 0x0143, 0x80 is a game that also runs on the dmg and 0xC0 a cgb only one
+pub fn is_cgb_rom(rom: &[u8]) -> bool {
+    rom.get(0x143).is_some_and(|flag| flag & 0x80 != 0)
+}
+
+/// everything the game boy color has on top of the dmg.
+///
+/// the selection registers (VBK, SVBK, BCPS, OCPS, KEY1) stay in the normal
+/// io area and get passed in, only the memory behind them is kept here.
+/// vram bank 0 and wram bank 0 are the normal memory too, and echo ram
+/// has already been turned into its wram address by the time it gets here
+pub struct Cgb {
+    // the second vram bank, holding more tiles and the bg map attributes
+    pub vram1: Vec<u8>,
+    // wram banks 1-7, switched into 0xD000-0xDFFF
+    pub wram: Vec<u8>,
+    // 8 palettes of 4 RGB555 colours each, little endian
+    pub bg_palettes: [u8; 64],
+    pub obj_palettes: [u8; 64],
+    pub hdma: Hdma,
+}
+
+/// the vram dma, which copies 16 byte blocks into vram. a general purpose
+/// dma copies everything straight away, an hblank dma a block every hblank.
+/// the cpu is halted while blocks are copied either way
+pub struct Hdma {
+    pub source: u16,
+    pub dest: u16,
+    // blocks left to copy in hblank, 0 when there isnt an hblank dma going
+    pub blocks: u8,
+    // what HDMA5 reads as while nothing is going, 0xFF once a dma has finished
+    pub status: u8,
+    // cpu cycles the cpu still has to sit out for the blocks already copied
+    pub stall: u32,
+}
+
+impl Default for Hdma {
+    fn default() -> Self {
+        Self { source: 0, dest: 0, blocks: 0, status: 0xFF, stall: 0 }
+    }
+}
+
+impl Hdma {
+    /// bit 7 is clear while an hblank dma is going, the rest is how many blocks are left minus one
+    pub fn read(&self) -> u8 {
+        match self.blocks {
+            0 => self.status,
+            blocks => blocks - 1,
+        }
+    }
+}
+
+impl Default for Cgb {
+    fn default() -> Self {
+        Self {
+            vram1: vec![0; 0x2000],
+            wram: vec![0; 7 * 0x1000],
+            // the boot rom leaves the background palettes white
+            bg_palettes: [0xFF; 64],
+            obj_palettes: [0; 64],
+            hdma: Hdma::default(),
+        }
+    }
+}
+
+impl Cgb {
+    /// where an address in 0xD000-0xDFFF is in `wram`. bank 0 picks bank 1
+    fn wram_index(io: &[u8], address: u16) -> usize {
+        let bank = (io[SVBK as usize] & 0b111).max(1) as usize;
+        (bank - 1) * 0x1000 + (address as usize & 0x0FFF)
+    }
+
+    /// the addresses which dont read from the normal memory, `None` for the rest
+    pub fn read(&self, io: &[u8], address: u16) -> Option<u8> {
+        match address {
+            0x8000..=0x9FFF if io[VBK as usize] & 1 == 1 => Some(self.vram1[address as usize - 0x8000]),
+            0xD000..=0xDFFF => Some(self.wram[Self::wram_index(io, address)]),
+            BCPD => Some(self.bg_palettes[(io[BCPS as usize] & 0x3F) as usize]),
+            OCPD => Some(self.obj_palettes[(io[OCPS as usize] & 0x3F) as usize]),
+            HDMA5 => Some(self.hdma.read()),
+            _ => None,
+        }
+    }
+
+    /// returns whether the write was dealt with here
+    pub fn write(&mut self, io: &mut [u8], address: u16, data: u8) -> bool {
+        match address {
+            0x8000..=0x9FFF if io[VBK as usize] & 1 == 1 => self.vram1[address as usize - 0x8000] = data,
+            0xD000..=0xDFFF => self.wram[Self::wram_index(io, address)] = data,
+            BCPD => write_palette(&mut self.bg_palettes, &mut io[BCPS as usize], data),
+            OCPD => write_palette(&mut self.obj_palettes, &mut io[OCPS as usize], data),
+            _ => return false,
+        }
+        true
+    }
+
+    /// a background colour as RGB555
+    pub fn bg_colour(&self, palette: u8, colour: u8) -> u16 {
+        palette_colour(&self.bg_palettes, palette, colour)
+    }
+    pub fn obj_colour(&self, palette: u8, colour: u8) -> u16 {
+        palette_colour(&self.obj_palettes, palette, colour)
+    }
+
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.bytes(&self.vram1);
+        writer.bytes(&self.wram);
+        writer.bytes(&self.bg_palettes);
+        writer.bytes(&self.obj_palettes);
+        writer.u16(self.hdma.source);
+        writer.u16(self.hdma.dest);
+        writer.u8(self.hdma.blocks);
+        writer.u8(self.hdma.status);
+        writer.u32(self.hdma.stall);
+    }
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let mut load = |into: &mut [u8]| -> Result<(), String> {
+            let data = reader.bytes()?;
+            if data.len() != into.len() {
+                return Err("save state cgb memory is the wrong size".to_string());
+            }
+            into.copy_from_slice(data);
+            Ok(())
+        };
+        load(&mut self.vram1)?;
+        load(&mut self.wram)?;
+        load(&mut self.bg_palettes)?;
+        load(&mut self.obj_palettes)?;
+        // version 2 was from before vram dma
+        if reader.version >= 3 {
+            self.hdma.source = reader.u16()?;
+            self.hdma.dest = reader.u16()?;
+            self.hdma.blocks = reader.u8()?;
+            self.hdma.status = reader.u8()?;
+            self.hdma.stall = reader.u32()?;
+        }
+        Ok(())
+    }
+}
+
+fn palette_colour(palettes: &[u8; 64], palette: u8, colour: u8) -> u16 {
+    let i = (palette as usize & 0b111) * 8 + (colour as usize & 0b11) * 2;
+    u16::from_le_bytes([palettes[i], palettes[i + 1]]) & 0x7FFF
+}
+
+/// BCPS/OCPS hold the index being written in the low 6 bits, and
+/// bit 7 makes it move on after every write
+fn write_palette(palettes: &mut [u8; 64], select: &mut u8, data: u8) {
+    palettes[(*select & 0x3F) as usize] = data;
+    if *select & 0x80 != 0 {
+        *select = 0x80 | ((*select + 1) & 0x3F);
+    }
+}
@@ -0,0 +1,187 @@
+/// a decoded cheat code
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Code {
+    // `ABC-DEF-GHI` replaces a rom byte, only when it holds `compare` if there is one
+    GameGenie { address: u16, value: u8, compare: Option<u8> },
+    // `01VVAAAA` writes to ram every frame. the first byte would pick the external
+    // ram bank, but there are no mbcs yet so it is kept and ignored
+    GameShark { bank: u8, address: u16, value: u8 },
+}
+
+impl Code {
+    pub fn parse(text: &str) -> Option<Self> {
+        // from_str_radix would let a sign through, and slicing needs every digit to be one byte
+        let is_hex = |s: &str| s.bytes().all(|b| b.is_ascii_hexdigit());
+        let hex = |s: &str| u32::from_str_radix(s, 16).ok();
+        if text.contains('-') {
+            // the digits are shuffled, ABC-DEF-GHI means value AB, address FCDE ^ F000
+            // and compare GI rotated right 2 ^ BA. H is only a check digit
+            let digits: String = text.split('-').collect();
+            if digits.len() != 6 && digits.len() != 9 || !is_hex(&digits) {
+                return None;
+            }
+            let d = |i: usize| hex(&digits[i..i + 1]).map(|n| n as u16);
+            let value = hex(&digits[0..2])? as u8;
+            let address = ((d(5)? << 12) | (d(2)? << 8) | (d(3)? << 4) | d(4)?) ^ 0xF000;
+            let compare = match digits.len() {
+                9 => Some(((d(6)? << 4 | d(8)?) as u8).rotate_right(2) ^ 0xBA),
+                _ => None,
+            };
+            return Some(Self::GameGenie { address, value, compare });
+        }
+        if text.len() != 8 || !is_hex(text) {
+            return None;
+        }
+        let bytes = hex(text)?.to_be_bytes();
+        // the address is stored little endian
+        Some(Self::GameShark { bank: bytes[0], value: bytes[1], address: u16::from_le_bytes([bytes[2], bytes[3]]) })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cheat {
+    pub code: Code,
+    // what it was written as, so it can be shown back
+    pub text: String,
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// the cheats for a rom, loaded from a file with one code per line and
+/// an optional name after it. `#` starts a comment.
+///
+/// ```text
+/// 00A-17B-C49 infinite lives
+/// 010FA0C0    start on level 15
+/// ```
+///
+/// they live in the memory, game genie codes are applied to rom reads
+/// as they happen and gameshark codes are written at the end of each frame
+#[derive(Default)]
+pub struct Cheats {
+    pub cheats: Vec<Cheat>,
+    // turns them all off without losing which ones are enabled
+    pub disabled: bool,
+}
+
+impl Cheats {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut cheats = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            cheats.add(code, name.trim()).map_err(|e| format!("line {}: {e}", i + 1))?;
+        }
+        Ok(cheats)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Err(e) => Err(format!("could not read {path}: {e}")),
+            Ok(text) => Self::parse(&text).map_err(|e| format!("{path} {e}")),
+        }
+    }
+
+    /// the cheat file sits next to the rom as `<rom>.cht`
+    pub fn for_rom(rom_path: &str) -> Result<Self, String> {
+        let path = std::path::Path::new(rom_path).with_extension("cht");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load(&path.to_string_lossy())
+    }
+
+    pub fn add(&mut self, code: &str, name: &str) -> Result<(), String> {
+        let Some(parsed) = Code::parse(&code.to_ascii_uppercase()) else {
+            return Err(format!("invalid cheat code {code}"));
+        };
+        self.cheats.push(Cheat { code: parsed, text: code.to_string(), name: name.to_string(), enabled: true });
+        Ok(())
+    }
+
+    fn active(&self) -> impl Iterator<Item = &Code> {
+        self.cheats.iter().filter(|c| c.enabled && !self.disabled).map(|c| &c.code)
+    }
+
+    /// what a rom read gives back once the game genie codes have had a go at it
+    pub fn patch_rom(&self, address: u16, value: u8) -> u8 {
+        for code in self.active() {
+            if let Code::GameGenie { address: a, value: new, compare } = *code {
+                if a == address && compare.is_none_or(|c| c == value) {
+                    return new;
+                }
+            }
+        }
+        value
+    }
+
+    /// the gameshark writes, as address and value. they go to whatever is
+    /// mapped at the address as the codes' bank isnt used
+    pub fn ram_writes(&self) -> Vec<(u16, u8)> {
+        self.active()
+            .filter_map(|code| match *code {
+                Code::GameShark { address, value, .. } => Some((address, value)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_genie_without_compare() {
+        // value 3E, address F12B ^ F000
+        assert_eq!(Code::parse("3E1-2BF"), Some(Code::GameGenie { address: 0x012B, value: 0x3E, compare: None }));
+    }
+
+    #[test]
+    fn game_genie_with_compare() {
+        // compare C9 rotated right 2 is 72, ^ BA is C8
+        assert_eq!(Code::parse("00A-17B-C49"), Some(Code::GameGenie { address: 0x4A17, value: 0x00, compare: Some(0xC8) }));
+    }
+
+    #[test]
+    fn game_genie_compare_is_checked() {
+        let mut cheats = Cheats::default();
+        cheats.add("00a-17b-c49", "").unwrap();
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0x00);
+        assert_eq!(cheats.patch_rom(0x4A17, 0x12), 0x12);
+        assert_eq!(cheats.patch_rom(0x4A18, 0xC8), 0xC8);
+        cheats.disabled = true;
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0xC8);
+    }
+
+    #[test]
+    fn gameshark() {
+        assert_eq!(Code::parse("010FA0C0"), Some(Code::GameShark { bank: 0x01, value: 0x0F, address: 0xC0A0 }));
+        let cheats = Cheats::parse("010FA0C0 start on level 15\n# a comment\n\n01FFA1C0").unwrap();
+        assert_eq!(cheats.ram_writes(), vec![(0xC0A0, 0x0F), (0xC0A1, 0xFF)]);
+    }
+
+    #[test]
+    fn invalid_codes() {
+        for code in ["", "00A-17B-C", "00A-17B-C4", "00G-17B-C49", "010FA0C", "010FA0C0F", "+10FA0C0", "ZZ0FA0C0"] {
+            assert_eq!(Code::parse(code), None, "{code}");
+        }
+    }
+
+    #[test]
+    fn non_ascii_codes() {
+        // these are the right number of bytes, slicing them used to land inside the É
+        assert_eq!(Code::parse("0É-17B-C49"), None);
+        assert_eq!(Code::parse("0É-17B"), None);
+        assert_eq!(Code::parse("01É0C0A"), None);
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        let error = Cheats::parse("010FA0C0\n0É-17B-C49 lives").err().unwrap();
+        assert!(error.starts_with("line 2:"), "{error}");
+    }
+}
@@ -3,6 +3,7 @@ use crate::memory::Memory;
 use crate::{combine_u8s, split_u16};
 use crate::opcodes::*;
 use crate::registers::*;
+use crate::savestate::{SaveState, StateWriter, StateReader};
 
 /// handles all the gameboy's logic and processing.
 /// It adds proper functionality to the `Registers` struct.
@@ -15,32 +16,51 @@ pub struct Cpu {
     // instruction, not after the current instruction
     scheduled_ime: bool,
     ime: bool,
-    // also for debug purposes, as it allows me to pinpoint
-    // which instructions may possibly be erring
-    used: Vec<u8>,
     cycles: u8,
 }
 
 impl Cpu {
     pub fn new(memory: Rc<RefCell<Memory>>) -> Self {
         Self {
-            regs: CpuRegisters::new(),
+            regs: CpuRegisters::post_boot(),
             memory,
             stopped: false,
             scheduled_ime: false,
             ime: false,
-            used: Vec::new(),
             cycles: 0,
         }
     }
 
+    pub fn ime(&self) -> bool {
+        self.ime
+    }
+    pub fn halted(&self) -> bool {
+        self.stopped
+    }
+    /// whether the next call to `process_next` will jump to an interrupt
+    /// rather than run an instruction
+    pub fn interrupt_pending(&self) -> bool {
+        let memory = self.memory.borrow();
+        self.ime && memory.peek(0xFF0F) & memory.peek(0xFFFF) & 0x1F != 0
+    }
+
     /// the read and write commands can read/write u8s to memory.
     /// the `read_u16` and `write_u16` simply make it more convenient
     /// and just end up calling the `read` and `write` commands
     fn read(&mut self, address: u16) -> u8 {
         // accessing memory takes time
         self.cycles += 4;
-        self.memory.borrow().read(address)
+        let mut memory = self.memory.borrow_mut();
+        memory.cpu_cycle();
+        memory.read(address)
+    }
+    /// the interrupt check takes the time of a read, but it peeks so that
+    /// watchpoints on IF and IE only go off when the game itself uses them
+    fn poll(&mut self, address: u16) -> u8 {
+        self.cycles += 4;
+        let mut memory = self.memory.borrow_mut();
+        memory.cpu_cycle();
+        memory.peek(address)
     }
     fn read_u16(&mut self, address: u16) -> u16 {
         combine_u8s(self.read(address), self.read(address+1))
@@ -48,7 +68,9 @@ impl Cpu {
     fn write(&mut self, address: u16, data: u8) {
         // reading memory takes time
         self.cycles += 4;
-        self.memory.borrow_mut().write(address, data);
+        let mut memory = self.memory.borrow_mut();
+        memory.cpu_cycle();
+        memory.write(address, data);
     }
     fn write_u16(&mut self, address: Option<u16>, data: u16) {
         let address = match address {
@@ -261,11 +283,18 @@ impl Cpu {
         self.cycles = 0;
 
         // check for possible interupts
-        let possible_interrupts = self.read(0xFF0F) & self.read(0xFFFF);
+        // only the bottom 5 bits are interrupts, the top of IF always reads as 1
+        let possible_interrupts = self.poll(0xFF0F) & self.poll(0xFFFF) & 0x1F;
         if possible_interrupts != 0 && self.ime {
             // due to priority, we want to handle the interrupt furthest to the right.
             // this will return the first one to handle
             let interrupt = possible_interrupts.trailing_zeros();
+            // handling it clears its flag, otherwise it would go straight off again
+            {
+                let mut memory = self.memory.borrow_mut();
+                let flags = memory.peek(0xFF0F);
+                memory.unchecked_write(0xFF0F, flags & !(1 << interrupt));
+            }
             match interrupt {
                 0 => self.call(true, Some(0x40)),
                 1 => self.call(true, Some(0x48)),
@@ -321,7 +350,7 @@ impl Cpu {
             0x0D => dec(&mut self.regs.c, &mut self.regs.f), // DEC C
             0x0E => self.regs.c = self.next_byte(), // LD C, n
             0x0F => rrc(&mut self.regs.a, &mut self.regs.f), // RRCA
-            0x10 => {self.stopped = true; self.next_byte();}, // STOP n
+            0x10 => {self.next_byte(); if !self.memory.borrow_mut().switch_speed() {self.stopped = true}}, // STOP n
             0x11 => {let w = self.next_word(); self.regs.set_de(w)} // LD DE, nn
             0x12 => self.write(self.regs.de(), self.regs.a), // LD (DE), A
             0x13 => self.regs.set_de(self.regs.de() + 1), // INC DE
@@ -355,7 +384,7 @@ impl Cpu {
             0x2F => self.cpl(), // CPL
             0x30 => self.jr(!self.regs.f.c_flag()), // JR NC, e
             0x31 => self.regs.sp = combine_u8s(self.next_byte(), self.next_byte()), // LD SP, nn
-            0x32 => {let hl = self.regs.hli(); self.write(hl, self.regs.a)} // LD (HL-), A
+            0x32 => {let hl = self.regs.hld(); self.write(hl, self.regs.a)} // LD (HL-), A
             0x33 => self.regs.sp += 1, // INC SP
             0x34 => {
                 let mut data = self.read(self.regs.hl());
@@ -527,4 +556,20 @@ impl Cpu {
         };
         run_prefixed(dst, &mut self.regs.f, instruction);
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for Cpu {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.regs.save_state(writer);
+        writer.bool(self.ime);
+        writer.bool(self.scheduled_ime);
+        writer.bool(self.stopped);
+    }
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.regs.load_state(reader)?;
+        self.ime = reader.bool()?;
+        self.scheduled_ime = reader.bool()?;
+        self.stopped = reader.bool()?;
+        Ok(())
+    }
+}
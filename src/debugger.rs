@@ -0,0 +1,589 @@
+use std::io::{self, BufRead, Write};
+
+use crate::args::Args;
+use crate::gameboy::{GameBoy, SCANLINES};
+use crate::disasm::disassemble;
+use crate::symbols::Symbols;
+use crate::callstack::CallStack;
+use crate::viewers::{self, Palette};
+use crate::ramsearch::{self, RamSearch, Compare};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// any cpu access to memory within `start..=end` stops execution
+#[derive(Clone, Copy)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+}
+impl Watchpoint {
+    pub fn matches(&self, address: u16, access: Access) -> bool {
+        let wanted = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        };
+        wanted && address >= self.start && address <= self.end
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct WatchHit {
+    pub address: u16,
+    pub access: Access,
+    pub value: u8,
+}
+
+#[derive(Clone, Copy)]
+pub enum Breakpoint {
+    Pc(u16),
+    // only stops when the address is in the given rom bank
+    Bank(u8, u16),
+    // stops before any instruction with this opcode
+    Opcode(u8),
+}
+
+/// why execution stopped
+enum Stop {
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+    Done,
+}
+
+/// a command line debugger driving the gameboy without a window.
+///
+/// commands are read from stdin, `help` lists them. addresses and values
+/// are always hexadecimal, with or without a `$` or `0x` in front.
+/// when symbols are loaded addresses can be given as labels instead.
+pub struct Debugger {
+    gameboy: GameBoy,
+    breakpoints: Vec<Breakpoint>,
+    // counted so `frame` has something to report
+    frames: u64,
+    symbols: Symbols,
+    calls: CallStack,
+    search: Option<RamSearch>,
+}
+
+const HELP: &str = "\
+c, continue            run until a breakpoint or watchpoint
+s, step [n]            run n instructions
+n, next                step over calls
+finish                 run until the current function returns
+frame [n]              run until the end of the nth frame
+line <ly>              run until the scanline starts
+b <addr>               break at an address or label
+b <bank>:<addr>        break at an address in a rom bank
+b op <opcode>          break before an opcode
+w <addr>[-<end>] [r|w|rw]  watch memory, rw by default
+bl                     list breakpoints and watchpoints
+d <n>, dw <n>          delete a breakpoint/watchpoint
+r, regs                show the registers
+x <addr> [len]         show memory, <bank>:<addr> checks the bank is mapped
+l [addr] [count]       disassemble, from pc by default
+bt                     show the call stack
+oam                    show the sprite attributes
+pal                    show the palettes
+vram <dir> [palette]   dump the vram viewers as pngs, the tiles through bgp by default
+set <reg> <value>      change a register
+poke <addr> <value>..  change memory, one byte after another
+search start [<a>-<b>] snapshot memory to search, all ram by default
+search <op> [value]    keep what is =, !=, <, >, <= or >= the last snapshot or a value
+search list            show what the search has left
+cheat                  list the cheats
+cheat <n>              turn a cheat on or off
+cheat add <code> [name]  add a game genie or gameshark code
+cheat on|off           turn every cheat on or off
+freeze <addr> [value]  hold memory at a value, its current one by default
+unfreeze <addr>|all    let memory change again
+q, quit                exit";
+
+impl Debugger {
+    pub fn new(gameboy: GameBoy, symbols: Symbols) -> Self {
+        Self { gameboy, breakpoints: Vec::new(), frames: 0, symbols, calls: CallStack::default(), search: None }
+    }
+
+    pub fn repl(&mut self) {
+        self.print_location();
+        let stdin = io::stdin();
+        loop {
+            print!("(gb) ");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            if words[0] == "q" || words[0] == "quit" {
+                return;
+            }
+            if let Err(e) = self.command(&words) {
+                println!("{e}");
+            }
+        }
+    }
+
+    fn command(&mut self, words: &[&str]) -> Result<(), String> {
+        let arg = |i: usize| -> Result<&str, String> {
+            words.get(i).copied().ok_or(format!("{} expects more arguments", words[0]))
+        };
+        match words[0] {
+            "help" | "h" => println!("{HELP}"),
+            "c" | "continue" => {
+                let stop = self.run_until(|_, _| false);
+                self.report(stop);
+            }
+            "s" | "step" => {
+                let count = match words.get(1) {
+                    None => 1,
+                    Some(n) => n.parse().map_err(|_| format!("invalid count {n}"))?,
+                };
+                let mut remaining: u32 = count.max(1);
+                let stop = self.run_until(|_, _| { remaining -= 1; remaining == 0 });
+                self.report(stop);
+            }
+            "n" | "next" => {
+                let stop = self.step_over();
+                self.report(stop);
+            }
+            "finish" => {
+                let stop = self.step_out();
+                self.report(stop);
+            }
+            "frame" => {
+                let mut remaining: u32 = match words.get(1) {
+                    None => 1,
+                    Some(n) => n.parse().map_err(|_| format!("invalid count {n}"))?,
+                };
+                remaining = remaining.max(1);
+                let stop = self.run_until(|_, frame_done| {
+                    if frame_done { remaining -= 1; }
+                    remaining == 0
+                });
+                self.report(stop);
+            }
+            "line" => {
+                let target = parse_number(arg(1)?)?;
+                // anything past the last scanline would never stop
+                if target as usize >= SCANLINES {
+                    return Err(format!("no scanline {}, they go up to {:X}", arg(1)?, SCANLINES - 1));
+                }
+                let target = target as u8;
+                // only stops once the scanline starts, not part way through it
+                let mut last_ly = self.gameboy.ly();
+                let stop = self.run_until(|gameboy, _| {
+                    let started = gameboy.ly() == target && last_ly != target;
+                    last_ly = gameboy.ly();
+                    started
+                });
+                self.report(stop);
+            }
+            "b" => {
+                let breakpoint = if arg(1)? == "op" {
+                    Breakpoint::Opcode(parse_number(arg(2)?)? as u8)
+                } else if let Some((bank, address)) = self.symbols.resolve(arg(1)?) {
+                    // labels in switchable banks should only stop in their own bank
+                    if (0x4000..0x8000).contains(&address) {
+                        Breakpoint::Bank(bank, address)
+                    } else {
+                        Breakpoint::Pc(address)
+                    }
+                } else if let Some((bank, address)) = arg(1)?.split_once(':') {
+                    Breakpoint::Bank(parse_number(bank)? as u8, parse_number(address)?)
+                } else {
+                    Breakpoint::Pc(parse_number(arg(1)?)?)
+                };
+                self.breakpoints.push(breakpoint);
+                println!("breakpoint {} set", self.breakpoints.len() - 1);
+            }
+            "w" => {
+                let (start, end) = match arg(1)?.split_once('-') {
+                    Some((start, end)) => (parse_number(start)?, parse_number(end)?),
+                    None => { let a = parse_number(arg(1)?)?; (a, a) }
+                };
+                let (read, write) = match words.get(2).copied().unwrap_or("rw") {
+                    "r" => (true, false),
+                    "w" => (false, true),
+                    "rw" => (true, true),
+                    other => return Err(format!("invalid access {other}")),
+                };
+                let mut memory = self.gameboy.memory.borrow_mut();
+                memory.watchpoints.push(Watchpoint { start, end, read, write });
+                println!("watchpoint {} set", memory.watchpoints.len() - 1);
+            }
+            "bl" => self.list_breakpoints(),
+            "d" => {
+                let index = parse_index(arg(1)?, self.breakpoints.len())?;
+                self.breakpoints.remove(index);
+            }
+            "dw" => {
+                let mut memory = self.gameboy.memory.borrow_mut();
+                let index = parse_index(arg(1)?, memory.watchpoints.len())?;
+                memory.watchpoints.remove(index);
+            }
+            "r" | "regs" => self.print_registers(),
+            "bt" => self.print_backtrace(),
+            "oam" => print!("{}", viewers::oam_table(&self.gameboy.memory.borrow())),
+            "pal" => print!("{}", viewers::palette_table(&self.gameboy.memory.borrow())),
+            "vram" => {
+                let palette = match words.get(2) {
+                    None => Palette::Bgp,
+                    Some(p) => Palette::parse(p).ok_or(format!("unknown palette {p}, try bgp, obp0, obp1 or raw"))?,
+                };
+                viewers::dump(&self.gameboy.memory.borrow(), arg(1)?, palette)?;
+                println!("vram written to {}", arg(1)?);
+            }
+            "x" => {
+                let address = self.parse_address(arg(1)?)?;
+                let len = match words.get(2) {
+                    None => 0x40,
+                    Some(l) => parse_number(l)?,
+                };
+                self.dump_memory(address, len);
+            }
+            "l" => {
+                let address = match words.get(1) {
+                    None => self.gameboy.cpu.regs.pc,
+                    Some(a) => self.parse_address(a)?,
+                };
+                let count = match words.get(2) {
+                    None => 10,
+                    Some(c) => c.parse().map_err(|_| format!("invalid count {c}"))?,
+                };
+                self.list_instructions(address, count);
+            }
+            "set" => self.set_register(arg(1)?, parse_number(arg(2)?)?)?,
+            "poke" => {
+                let address = self.parse_address(arg(1)?)?;
+                arg(2)?;
+                let mut memory = self.gameboy.memory.borrow_mut();
+                for (i, value) in words[2..].iter().enumerate() {
+                    memory.unchecked_write(address.wrapping_add(i as u16), parse_number(value)? as u8);
+                }
+            }
+            "search" => self.search(&words[1..])?,
+            "cheat" => self.cheat(&words[1..])?,
+            "freeze" => {
+                let address = self.parse_address(arg(1)?)?;
+                let mut memory = self.gameboy.memory.borrow_mut();
+                let value = match words.get(2) {
+                    None => memory.peek(address),
+                    Some(v) => parse_number(v)? as u8,
+                };
+                memory.freeze(address, value);
+                println!("{address:04X} frozen at {value:02X}");
+            }
+            "unfreeze" => {
+                match arg(1)? {
+                    "all" => self.gameboy.memory.borrow_mut().frozen.clear(),
+                    a => {
+                        let address = self.parse_address(a)?;
+                        self.gameboy.memory.borrow_mut().unfreeze(address);
+                    }
+                }
+            }
+            other => return Err(format!("unknown command {other}, try help")),
+        }
+        Ok(())
+    }
+
+    /// steps until `done` returns true, or something stops it first.
+    /// `done` is given whether the step finished a frame
+    fn run_until(&mut self, mut done: impl FnMut(&GameBoy, bool) -> bool) -> Stop {
+        loop {
+            self.calls.before_step(&self.gameboy.cpu, &self.gameboy.memory.borrow());
+            let frame_done = self.gameboy.step();
+            self.calls.after_step(&self.gameboy.cpu, &self.gameboy.memory.borrow());
+            if frame_done {
+                self.frames += 1;
+            }
+            if let Some(hit) = self.gameboy.memory.borrow().take_watch_hit() {
+                return Stop::Watchpoint(hit);
+            }
+            if let Some(index) = self.breakpoint_hit() {
+                return Stop::Breakpoint(index);
+            }
+            if done(&self.gameboy, frame_done) {
+                return Stop::Done;
+            }
+        }
+    }
+
+    fn breakpoint_hit(&self) -> Option<usize> {
+        let pc = self.gameboy.cpu.regs.pc;
+        let memory = self.gameboy.memory.borrow();
+        self.breakpoints.iter().position(|b| match *b {
+            Breakpoint::Pc(address) => address == pc,
+            Breakpoint::Bank(bank, address) => address == pc && memory.rom_bank(pc) == bank,
+            Breakpoint::Opcode(opcode) => memory.peek(pc) == opcode,
+        })
+    }
+
+    /// calls and rsts are ran until they come back, anything else is a normal step
+    fn step_over(&mut self) -> Stop {
+        let pc = self.gameboy.cpu.regs.pc;
+        let opcode = self.gameboy.memory.borrow().peek(pc);
+        let return_address = match opcode {
+            0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => pc.wrapping_add(3),
+            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => pc.wrapping_add(1),
+            _ => return self.run_until(|_, _| true),
+        };
+        let sp = self.gameboy.cpu.regs.sp;
+        self.run_until(|gameboy, _| gameboy.cpu.regs.pc == return_address && gameboy.cpu.regs.sp >= sp)
+    }
+
+    /// runs until the stack pointer goes above where it was, which means
+    /// the current function has returned
+    fn step_out(&mut self) -> Stop {
+        let sp = self.gameboy.cpu.regs.sp;
+        // stopped on a return already, which has to be seen going past too
+        let mut last_opcode = self.gameboy.memory.borrow().peek(self.gameboy.cpu.regs.pc);
+        self.run_until(|gameboy, _| {
+            let returned = matches!(last_opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9) && gameboy.cpu.regs.sp > sp;
+            last_opcode = gameboy.memory.borrow().peek(gameboy.cpu.regs.pc);
+            returned
+        })
+    }
+
+    fn report(&self, stop: Stop) {
+        match stop {
+            Stop::Breakpoint(i) => println!("hit breakpoint {i}"),
+            Stop::Watchpoint(hit) => {
+                let access = match hit.access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                };
+                println!("watchpoint: {access} of {:02X} at {:04X}", hit.value, hit.address);
+            }
+            Stop::Done => (),
+        }
+        self.print_location();
+    }
+
+    /// the address with its label after it, if it has one
+    fn describe(&self, address: u16) -> String {
+        let bank = self.gameboy.memory.borrow().rom_bank(address);
+        match self.symbols.describe(bank, address) {
+            None => format!("{bank:02X}:{address:04X}"),
+            Some(label) => format!("{bank:02X}:{address:04X} <{label}>"),
+        }
+    }
+
+    /// labels are tried before numbers, since something like `Add` is also valid hex.
+    /// a `<bank>:` in front is only allowed when that bank is the one mapped in
+    fn parse_address(&self, text: &str) -> Result<u16, String> {
+        if let Some((_, address)) = self.symbols.resolve(text) {
+            return Ok(address);
+        }
+        let Some((bank, address)) = text.split_once(':') else { return parse_number(text) };
+        let (bank, address) = (parse_number(bank)? as u8, parse_number(address)?);
+        let mapped = self.gameboy.memory.borrow().rom_bank(address);
+        if address < 0x8000 && bank != mapped {
+            return Err(format!("bank {bank:02X} isnt mapped in, {mapped:02X} is"));
+        }
+        Ok(address)
+    }
+
+    fn cheat(&mut self, words: &[&str]) -> Result<(), String> {
+        let cheats = &mut self.gameboy.memory.borrow_mut().cheats;
+        match words.first().copied() {
+            None => {
+                for (i, cheat) in cheats.cheats.iter().enumerate() {
+                    let state = if cheat.enabled { "on " } else { "off" };
+                    println!("{i}: {state} {:<12} {}", cheat.text, cheat.name);
+                }
+                if cheats.disabled {
+                    println!("all cheats are turned off");
+                }
+            }
+            Some("add") => {
+                let code = words.get(1).ok_or("cheat add expects a code")?;
+                cheats.add(code, &words[2..].join(" "))?;
+            }
+            Some("on") => cheats.disabled = false,
+            Some("off") => cheats.disabled = true,
+            Some(index) => {
+                let index = parse_index(index, cheats.cheats.len())?;
+                let cheat = &mut cheats.cheats[index];
+                cheat.enabled = !cheat.enabled;
+                println!("cheat {index} {}", if cheat.enabled { "on" } else { "off" });
+            }
+        }
+        Ok(())
+    }
+
+    fn search(&mut self, words: &[&str]) -> Result<(), String> {
+        let memory = self.gameboy.memory.borrow();
+        match words.first().copied() {
+            Some("start") => {
+                let ranges = match words.get(1) {
+                    None => ramsearch::RAM.to_vec(),
+                    Some(range) => match range.split_once('-') {
+                        None => return Err(format!("invalid range {range}")),
+                        Some((start, end)) => vec![(parse_number(start)?, parse_number(end)?)],
+                    },
+                };
+                self.search = Some(RamSearch::new(&memory, &ranges));
+            }
+            Some("list") => {
+                let search = self.search.as_ref().ok_or("no search started")?;
+                for (address, _) in search.candidates.iter().take(20) {
+                    println!("{address:04X}: {:02X}", memory.peek(*address));
+                }
+                if search.candidates.len() > 20 {
+                    println!("...");
+                }
+            }
+            Some(op) => {
+                let compare = Compare::parse(op).ok_or(format!("unknown search {op}"))?;
+                let value = match words.get(1) {
+                    None => None,
+                    Some(v) => Some(parse_number(v)? as u8),
+                };
+                let search = self.search.as_mut().ok_or("no search started")?;
+                search.filter(&memory, compare, value);
+            }
+            None => return Err("search expects more arguments".to_string()),
+        }
+        if let Some(search) = &self.search {
+            println!("{} addresses left", search.candidates.len());
+        }
+        Ok(())
+    }
+
+    fn print_location(&self) {
+        let pc = self.gameboy.cpu.regs.pc;
+        let memory = self.gameboy.memory.borrow();
+        let instruction = disassemble(|a| memory.peek(a), pc);
+        let text = instruction.text_with_symbols(&self.symbols, memory.rom_bank(pc));
+        println!("{}  {text}   (frame {}, ly {})", self.describe(pc), self.frames, self.gameboy.ly());
+    }
+
+    fn list_instructions(&self, mut address: u16, count: usize) {
+        let memory = self.gameboy.memory.borrow();
+        for _ in 0..count {
+            let bank = memory.rom_bank(address);
+            if let Some(label) = self.symbols.label_at(bank, address) {
+                println!("{label}:");
+            }
+            let instruction = disassemble(|a| memory.peek(a), address);
+            let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{b:02X}")).collect();
+            println!("{bank:02X}:{address:04X}  {:<9} {}", bytes.join(" "), instruction.text_with_symbols(&self.symbols, bank));
+            address = address.wrapping_add(instruction.bytes.len() as u16);
+        }
+    }
+
+    /// the innermost call comes first, the same as gdb
+    fn print_backtrace(&self) {
+        println!("#0  {}", self.describe(self.gameboy.cpu.regs.pc));
+        for (i, frame) in self.calls.frames.iter().rev().enumerate() {
+            println!("#{}  {}  called {}", i + 1, self.describe(frame.return_address), self.describe(frame.function));
+        }
+    }
+
+    fn print_registers(&self) {
+        let regs = &self.gameboy.cpu.regs;
+        let flag = |set: bool, c: char| if set { c } else { '-' };
+        println!(
+            "A:{:02X} F:{}{}{}{} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} IME:{} HALT:{}",
+            regs.a,
+            flag(regs.f.z_flag(), 'Z'), flag(regs.f.n_flag(), 'N'), flag(regs.f.h_flag(), 'H'), flag(regs.f.c_flag(), 'C'),
+            regs.b, regs.c, regs.d, regs.e, regs.h, regs.l, regs.sp, regs.pc,
+            self.gameboy.cpu.ime() as u8, self.gameboy.cpu.halted() as u8,
+        );
+    }
+
+    fn set_register(&mut self, reg: &str, value: u16) -> Result<(), String> {
+        let regs = &mut self.gameboy.cpu.regs;
+        match reg {
+            "a" => regs.a = value as u8,
+            "f" => { let a = regs.a; regs.set_af(crate::combine_u8s(value as u8, a)) }
+            "b" => regs.b = value as u8,
+            "c" => regs.c = value as u8,
+            "d" => regs.d = value as u8,
+            "e" => regs.e = value as u8,
+            "h" => regs.h = value as u8,
+            "l" => regs.l = value as u8,
+            "af" => regs.set_af(value),
+            "bc" => regs.set_bc(value),
+            "de" => regs.set_de(value),
+            "hl" => regs.set_hl(value),
+            "sp" => regs.sp = value,
+            "pc" => regs.pc = value,
+            _ => return Err(format!("unknown register {reg}")),
+        }
+        Ok(())
+    }
+
+    /// rom rows get their bank in front and every row gets its bytes as text,
+    /// frozen bytes are marked with a `*`
+    fn dump_memory(&self, address: u16, len: u16) {
+        let memory = self.gameboy.memory.borrow();
+        for row in (0..len).step_by(16) {
+            let start = address.wrapping_add(row);
+            let addresses: Vec<u16> = (0..16.min(len - row)).map(|i| start.wrapping_add(i)).collect();
+            let bytes: Vec<String> = addresses.iter()
+                .map(|a| {
+                    let frozen = if memory.frozen.iter().any(|(f, _)| f == a) { '*' } else { ' ' };
+                    format!("{:02X}{frozen}", memory.peek(*a))
+                })
+                .collect();
+            let text: String = addresses.iter()
+                .map(|a| match memory.peek(*a) {
+                    c @ 0x20..=0x7E => c as char,
+                    _ => '.',
+                })
+                .collect();
+            let location = if start < 0x8000 { format!("{:02X}:{start:04X}", memory.rom_bank(start)) } else { format!("   {start:04X}") };
+            println!("{location}  {:<48} {text}", bytes.join(""));
+        }
+    }
+
+    fn list_breakpoints(&self) {
+        for (i, b) in self.breakpoints.iter().enumerate() {
+            match b {
+                Breakpoint::Pc(address) => println!("b{i}: {address:04X}"),
+                Breakpoint::Bank(bank, address) => println!("b{i}: {bank:02X}:{address:04X}"),
+                Breakpoint::Opcode(opcode) => println!("b{i}: opcode {opcode:02X}"),
+            }
+        }
+        for (i, w) in self.gameboy.memory.borrow().watchpoints.iter().enumerate() {
+            let access = match (w.read, w.write) {
+                (true, true) => "rw",
+                (true, false) => "r",
+                _ => "w",
+            };
+            println!("w{i}: {:04X}-{:04X} {access}", w.start, w.end);
+        }
+    }
+}
+
+/// runs the rom under the debugger instead of in a window
+pub fn run(args: &Args) {
+    let mut gameboy = GameBoy::new(args.rom());
+    gameboy.tracer = args.tracer();
+    gameboy.profiler = args.profiler();
+    gameboy.memory.borrow_mut().cheats = args.cheats();
+    gameboy.memory.borrow_mut().lax_access = args.lax_access;
+    gameboy.memory.borrow_mut().doctor_ly = args.trace_doctor;
+    Debugger::new(gameboy, args.symbols()).repl();
+}
+
+pub fn parse_number(text: &str) -> Result<u16, String> {
+    let digits = text.trim_start_matches('$').trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid number {text}"))
+}
+fn parse_index(text: &str, len: usize) -> Result<usize, String> {
+    match text.parse() {
+        Ok(i) if i < len => Ok(i),
+        _ => Err(format!("no entry {text}")),
+    }
+}
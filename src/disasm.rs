@@ -0,0 +1,165 @@
+use crate::symbols::Symbols;
+
+/// turns SM83 machine code back into assembly, using the same syntax
+/// as RGBDS so the output can be assembled again.
+///
+/// the decoding follows the way the opcodes are laid out in the table,
+/// most of them split into `xx yyy zzz` bits where y and z pick the registers.
+pub struct Instruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    // the address the instruction jumps to or accesses, if it has one
+    pub operand: Option<u16>,
+}
+
+impl Instruction {
+    /// the text with its address swapped for a label where there is one.
+    /// `bank` is the bank the instruction itself is in
+    pub fn text_with_symbols(&self, symbols: &Symbols, bank: u8) -> String {
+        let Some(operand) = self.operand else { return self.text.clone() };
+        // 16 bit loads might just be numbers, so only exact matches are trusted
+        let label = match self.bytes[0] {
+            0x01 | 0x11 | 0x21 | 0x31 => symbols.label_at(bank, operand).map(|l| l.to_string()),
+            _ => symbols.describe(bank, operand),
+        };
+        match label {
+            None => self.text.clone(),
+            Some(label) => self.text.replace(&format!("${operand:04X}"), &label),
+        }
+    }
+}
+
+const R8: [&str; 8] = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+const R16: [&str; 4] = ["bc", "de", "hl", "sp"];
+const R16_STACK: [&str; 4] = ["bc", "de", "hl", "af"];
+const R16_MEMORY: [&str; 4] = ["[bc]", "[de]", "[hl+]", "[hl-]"];
+const CONDITION: [&str; 4] = ["nz", "z", "nc", "c"];
+const ALU: [&str; 8] = ["add a,", "adc a,", "sub a,", "sbc a,", "and a,", "xor a,", "or a,", "cp a,"];
+const CB_SHIFTS: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+
+/// how many bytes the instruction starting with this opcode takes up
+pub fn instruction_length(opcode: u8) -> u16 {
+    match opcode {
+        0xCB => 2,
+        0x01 | 0x11 | 0x21 | 0x31 | 0x08 | 0xC2 | 0xC3 | 0xC4 | 0xCA | 0xCC | 0xCD | 0xD2 | 0xD4 | 0xDA | 0xDC | 0xEA | 0xFA => 3,
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 => 2,
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE | 0xE0 | 0xF0 | 0xE8 | 0xF8 => 2,
+        _ => 1,
+    }
+}
+
+/// decodes the instruction at the address, `read` is how bytes are fetched
+/// so this works on both rom files and live memory
+pub fn disassemble(read: impl Fn(u16) -> u8, address: u16) -> Instruction {
+    let opcode = read(address);
+    let length = instruction_length(opcode);
+    let bytes: Vec<u8> = (0..length).map(|i| read(address.wrapping_add(i))).collect();
+    let n8 = bytes.get(1).copied().unwrap_or(0);
+    let n16 = crate::combine_u8s(n8, bytes.get(2).copied().unwrap_or(0));
+    // relative jumps are shown as where they end up
+    let target = address.wrapping_add(2).wrapping_add_signed(n8 as i8 as i16);
+
+    let (x, y, z) = (opcode >> 6, ((opcode >> 3) & 7) as usize, (opcode & 7) as usize);
+    let (p, q) = (y >> 1, y & 1);
+    let text = match opcode {
+        0x00 => "nop".to_string(),
+        0x08 => format!("ld [${n16:04X}], sp"),
+        0x10 => "stop".to_string(),
+        0x18 => format!("jr ${target:04X}"),
+        0x20 | 0x28 | 0x30 | 0x38 => format!("jr {}, ${target:04X}", CONDITION[y - 4]),
+        0x07 => "rlca".to_string(),
+        0x0F => "rrca".to_string(),
+        0x17 => "rla".to_string(),
+        0x1F => "rra".to_string(),
+        0x27 => "daa".to_string(),
+        0x2F => "cpl".to_string(),
+        0x37 => "scf".to_string(),
+        0x3F => "ccf".to_string(),
+        0x76 => "halt".to_string(),
+        _ if x == 0 => match z {
+            1 if q == 0 => format!("ld {}, ${n16:04X}", R16[p]),
+            1 => format!("add hl, {}", R16[p]),
+            2 if q == 0 => format!("ld {}, a", R16_MEMORY[p]),
+            2 => format!("ld a, {}", R16_MEMORY[p]),
+            3 if q == 0 => format!("inc {}", R16[p]),
+            3 => format!("dec {}", R16[p]),
+            4 => format!("inc {}", R8[y]),
+            5 => format!("dec {}", R8[y]),
+            _ => format!("ld {}, ${n8:02X}", R8[y]),
+        },
+        _ if x == 1 => format!("ld {}, {}", R8[y], R8[z]),
+        _ if x == 2 => format!("{} {}", ALU[y], R8[z]),
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => format!("ret {}", CONDITION[y]),
+        0xC2 | 0xCA | 0xD2 | 0xDA => format!("jp {}, ${n16:04X}", CONDITION[y]),
+        0xC4 | 0xCC | 0xD4 | 0xDC => format!("call {}, ${n16:04X}", CONDITION[y]),
+        0xC1 | 0xD1 | 0xE1 | 0xF1 => format!("pop {}", R16_STACK[p]),
+        0xC5 | 0xD5 | 0xE5 | 0xF5 => format!("push {}", R16_STACK[p]),
+        0xC3 => format!("jp ${n16:04X}"),
+        0xC9 => "ret".to_string(),
+        0xCD => format!("call ${n16:04X}"),
+        0xD9 => "reti".to_string(),
+        0xE0 => format!("ldh [${:04X}], a", 0xFF00 | n8 as u16),
+        0xE2 => "ldh [c], a".to_string(),
+        0xE8 => format!("add sp, {}", n8 as i8),
+        0xE9 => "jp hl".to_string(),
+        0xEA => format!("ld [${n16:04X}], a"),
+        0xF0 => format!("ldh a, [${:04X}]", 0xFF00 | n8 as u16),
+        0xF2 => "ldh a, [c]".to_string(),
+        0xF3 => "di".to_string(),
+        0xF8 if (n8 as i8) < 0 => format!("ld hl, sp - {}", -(n8 as i8 as i16)),
+        0xF8 => format!("ld hl, sp + {n8}"),
+        0xF9 => "ld sp, hl".to_string(),
+        0xFA => format!("ld a, [${n16:04X}]"),
+        0xFB => "ei".to_string(),
+        0xCB => {
+            let (x, y, z) = (n8 >> 6, (n8 >> 3) & 7, (n8 & 7) as usize);
+            match x {
+                0 => format!("{} {}", CB_SHIFTS[y as usize], R8[z]),
+                1 => format!("bit {y}, {}", R8[z]),
+                2 => format!("res {y}, {}", R8[z]),
+                _ => format!("set {y}, {}", R8[z]),
+            }
+        }
+        _ if z == 6 => format!("{} ${n8:02X}", ALU[y]),
+        _ if z == 7 => format!("rst ${:02X}", y * 8),
+        // the 11 opcodes which dont exist
+        _ => format!("db ${opcode:02X}"),
+    };
+    let operand = match opcode {
+        0x18 | 0x20 | 0x28 | 0x30 | 0x38 => Some(target),
+        0x01 | 0x11 | 0x21 | 0x31 | 0x08 | 0xC2 | 0xC3 | 0xC4 | 0xCA | 0xCC | 0xCD | 0xD2 | 0xD4 | 0xDA | 0xDC | 0xEA | 0xFA => Some(n16),
+        0xE0 | 0xF0 => Some(0xFF00 | n8 as u16),
+        _ => None,
+    };
+    Instruction { address, bytes, text, operand }
+}
+
+/// disassembles every instruction which starts inside `start..end`
+pub fn disassemble_range(read: impl Fn(u16) -> u8, start: u16, end: u16) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut address = start as u32;
+    while address < end as u32 {
+        let instruction = disassemble(&read, address as u16);
+        address += instruction.bytes.len() as u32;
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+/// which bank and address a byte in the rom file would be seen at by the cpu
+pub fn rom_location(offset: usize) -> (usize, u16) {
+    let bank = offset / 0x4000;
+    let address = if bank == 0 { offset } else { 0x4000 + offset % 0x4000 };
+    (bank, address as u16)
+}
+
+/// disassembles part of a rom file. the range is given as file offsets and
+/// may not cross a bank boundary, since the addresses would stop making sense
+pub fn disassemble_rom(rom: &[u8], start: usize, end: usize) -> Vec<Instruction> {
+    let (bank, address) = rom_location(start);
+    let end = end.min(rom.len()).min((bank + 1) * 0x4000);
+    let base = start - address as usize;
+    let read = |a: u16| rom.get(base + a as usize).copied().unwrap_or(0);
+    disassemble_range(read, address, (end - base) as u16)
+}
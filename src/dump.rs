@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+use crate::framebuffer::Framebuffer;
+use crate::pacing::{CYCLES_PER_FRAME, CLOCK_SPEED};
+
+/// raw frames are just the RGBA bytes one after another, y4m adds a header
+/// with the size and exact frame rate so encoders need telling nothing
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum VideoFormat {
+    Rgba,
+    Y4m,
+}
+
+/// writes every frame and its audio out uncompressed for an encoder like
+/// ffmpeg to mux afterwards, or as it goes if the paths are named pipes.
+///
+/// everything is timed by the gameboy's clock, a frame is exactly
+/// 70224/4194304 seconds (59.7275 fps) and the audio has exactly as many
+/// samples as fit in the frames so far, so the two never drift apart.
+///
+/// the core doesnt emulate the sound hardware yet, so the audio is silence
+/// padded out to the right length. that still gives the encoder a track
+/// with the right timing to mux against
+pub struct Dumper {
+    video: Option<(VideoFormat, BufWriter<File>)>,
+    audio: Option<BufWriter<File>>,
+    // whether the audio has a wav header to fill in at the end
+    wav: bool,
+    timestamps: Option<BufWriter<File>>,
+    rate: u32,
+    frames: u64,
+    samples: u64,
+}
+
+const CHANNELS: u16 = 2;
+
+fn create(path: &str) -> Result<BufWriter<File>, String> {
+    File::create(path).map(BufWriter::new).map_err(|e| format!("could not create {path}: {e}"))
+}
+
+impl Dumper {
+    /// video ending in .y4m is written as y4m, anything else as raw RGBA. audio is 16 bit stereo
+    /// pcm at `rate`, in a wav file if it ends in .wav. the timestamps are mkvmerge's v2 format
+    pub fn new(video: Option<&str>, audio: Option<&str>, timestamps: Option<&str>, rate: u32) -> Result<Self, String> {
+        let video = match video {
+            None => None,
+            Some(path) => {
+                let format = if path.ends_with(".y4m") { VideoFormat::Y4m } else { VideoFormat::Rgba };
+                Some((format, create(path)?))
+            }
+        };
+        let wav = audio.is_some_and(|path| path.ends_with(".wav"));
+        let mut audio = match audio {
+            None => None,
+            Some(path) => Some(create(path)?),
+        };
+        if let (true, Some(out)) = (wav, &mut audio) {
+            // the sizes arent known yet, they are left as big as they go in case this is a pipe
+            out.write_all(&wav_header(rate, u32::MAX - 36)).map_err(|e| format!("could not write the audio: {e}"))?;
+        }
+        let mut timestamps = match timestamps {
+            None => None,
+            Some(path) => Some(create(path)?),
+        };
+        if let Some(out) = &mut timestamps {
+            writeln!(out, "# timecode format v2").map_err(|e| format!("could not write the timestamps: {e}"))?;
+        }
+        Ok(Self { video, audio, wav, timestamps, rate, frames: 0, samples: 0 })
+    }
+
+    /// when the frame starts, in seconds
+    pub fn timestamp(frame: u64) -> f64 {
+        frame as f64 * CYCLES_PER_FRAME as f64 / CLOCK_SPEED as f64
+    }
+
+    /// adds a frame along with the interleaved samples the core made during it.
+    /// anything short of a whole frame's worth of samples is filled with silence
+    pub fn frame(&mut self, frame: &Framebuffer, samples: &[i16]) -> Result<(), String> {
+        if let Some((format, out)) = &mut self.video {
+            let written = match format {
+                VideoFormat::Rgba => out.write_all(&frame.to_rgba()),
+                VideoFormat::Y4m => write_y4m(out, frame, self.frames == 0),
+            };
+            written.map_err(|e| format!("could not write the video: {e}"))?;
+        }
+        if let Some(out) = &mut self.timestamps {
+            writeln!(out, "{:.6}", Self::timestamp(self.frames) * 1000.0)
+                .map_err(|e| format!("could not write the timestamps: {e}"))?;
+        }
+        self.frames += 1;
+
+        // the samples that fit in all the frames so far, so rounding never builds up
+        let total = self.frames * CYCLES_PER_FRAME as u64 * self.rate as u64 / CLOCK_SPEED as u64;
+        let wanted = (total - self.samples) as usize * CHANNELS as usize;
+        self.samples = total;
+        if let Some(out) = &mut self.audio {
+            let mut bytes = Vec::with_capacity(wanted * 2);
+            for i in 0..wanted {
+                bytes.extend(samples.get(i).copied().unwrap_or(0).to_le_bytes());
+            }
+            out.write_all(&bytes).map_err(|e| format!("could not write the audio: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// flushes everything and fills in the wav header's sizes, which a pipe cant go back for
+    pub fn finish(mut self) -> Result<u64, String> {
+        if let Some((_, out)) = &mut self.video {
+            out.flush().map_err(|e| format!("could not write the video: {e}"))?;
+        }
+        if let Some(out) = &mut self.timestamps {
+            out.flush().map_err(|e| format!("could not write the timestamps: {e}"))?;
+        }
+        if let Some(out) = &mut self.audio {
+            out.flush().map_err(|e| format!("could not write the audio: {e}"))?;
+            if self.wav {
+                let size = (self.samples * CHANNELS as u64 * 2).min((u32::MAX - 36) as u64) as u32;
+                let file = out.get_mut();
+                if file.seek(SeekFrom::Start(0)).is_ok() {
+                    file.write_all(&wav_header(self.rate, size)).map_err(|e| format!("could not write the audio: {e}"))?;
+                }
+            }
+        }
+        Ok(self.frames)
+    }
+}
+
+/// a canonical 44 byte header for 16 bit pcm with `size` bytes of samples
+fn wav_header(rate: u32, size: u32) -> Vec<u8> {
+    let block = CHANNELS * 2;
+    let mut header = b"RIFF".to_vec();
+    header.extend((36 + size).to_le_bytes());
+    header.extend(b"WAVEfmt ");
+    header.extend(16u32.to_le_bytes());
+    header.extend(1u16.to_le_bytes());
+    header.extend(CHANNELS.to_le_bytes());
+    header.extend(rate.to_le_bytes());
+    header.extend((rate * block as u32).to_le_bytes());
+    header.extend(block.to_le_bytes());
+    header.extend(16u16.to_le_bytes());
+    header.extend(b"data");
+    header.extend(size.to_le_bytes());
+    header
+}
+
+/// full resolution 4:4:4 so single pixels keep their colour, in limited range bt.601
+fn write_y4m(out: &mut impl Write, frame: &Framebuffer, first: bool) -> std::io::Result<()> {
+    if first {
+        writeln!(out, "YUV4MPEG2 W{} H{} F{CLOCK_SPEED}:{CYCLES_PER_FRAME} Ip A1:1 C444", frame.width, frame.height)?;
+    }
+    let rgba = frame.to_rgba();
+    let count = rgba.len() / 4;
+    let mut planes = vec![0; count * 3];
+    for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+        let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+        planes[i] = (16 + ((66 * r + 129 * g + 25 * b + 128) >> 8)) as u8;
+        planes[count + i] = (128 + ((-38 * r - 74 * g + 112 * b + 128) >> 8)) as u8;
+        planes[count * 2 + i] = (128 + ((112 * r - 94 * g - 18 * b + 128) >> 8)) as u8;
+    }
+    out.write_all(b"FRAME\n")?;
+    out.write_all(&planes)
+}
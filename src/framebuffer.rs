@@ -0,0 +1,62 @@
+use crate::{pallete_to_rgba, png};
+
+/// a finished frame from the core, see `GameBoy::run_frame`.
+///
+/// pixels are stored the way the ppu draws them (see `pallete_to_rgba`),
+/// going left to right then top to bottom. sgb frames include the border
+/// so they are bigger than the gameboy's screen
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u16>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32, pixels: Vec<u16>) -> Self {
+        if pixels.len() != (width * height) as usize {
+            panic!("frame doesnt match its size");
+        }
+        Self { width, height, pixels }
+    }
+
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in &self.pixels {
+            let (r, g, b) = pallete_to_rgba(*pixel);
+            rgba.extend([r, g, b, 255]);
+        }
+        rgba
+    }
+
+    /// every pixel becomes a `scale` by `scale` square, returns the new size alongside
+    pub fn scaled_rgba(&self, scale: u32) -> (u32, u32, Vec<u8>) {
+        let rgba = self.to_rgba();
+        if scale <= 1 {
+            return (self.width, self.height, rgba);
+        }
+        let (width, height) = (self.width * scale, self.height * scale);
+        let mut scaled = Vec::with_capacity((width * height * 4) as usize);
+        for row in rgba.chunks_exact(self.width as usize * 4) {
+            let mut line = Vec::with_capacity(width as usize * 4);
+            for pixel in row.chunks_exact(4) {
+                for _ in 0..scale {
+                    line.extend(pixel);
+                }
+            }
+            for _ in 0..scale {
+                scaled.extend(&line);
+            }
+        }
+        (width, height, scaled)
+    }
+
+    pub fn to_png(&self, scale: u32) -> Vec<u8> {
+        let (width, height, rgba) = self.scaled_rgba(scale);
+        png::encode(width, height, &rgba)
+    }
+
+    pub fn save_png(&self, path: &str, scale: u32) -> Result<(), String> {
+        std::fs::write(path, self.to_png(scale)).map_err(|e| format!("could not write {path}: {e}"))
+    }
+}
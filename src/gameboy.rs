@@ -0,0 +1,353 @@
+use std::{rc::Rc, cell::RefCell};
+use crate::cpu::Cpu;
+use crate::ppu::Ppu;
+use crate::memory::Memory;
+use crate::savestate::{SaveState, StateWriter, StateReader};
+use crate::trace::Tracer;
+use crate::profiler::Profiler;
+use crate::sgb::{SGB_WIDTH, SGB_HEIGHT};
+use crate::link::{self, Cable};
+use crate::framebuffer::Framebuffer;
+use crate::io;
+
+// this number represents the number of cycles which each scanline will use up
+pub const MAXCYCLES: usize = 456;
+// the visible scanlines and then vblank
+pub const SCANLINES: usize = 154;
+// how far into a visible scanline mode 0 (hblank) starts, after the oam scan and drawing
+const HBLANK_START: usize = 80 + 172;
+
+pub const SCREEN_WIDTH: u32 = 160;
+pub const SCREEN_HEIGHT: u32 = 144;
+
+/// all the pillars of a gameboy emulator in one place.
+///
+/// this doesnt know anything about windows or inputs so it can
+/// be driven by the frontend or ran headless.
+pub struct GameBoy {
+    pub memory: Rc<RefCell<Memory>>,
+    pub cpu: Cpu,
+    pub ppu: Ppu,
+    // the scanline currently being processed and how far into it we are
+    ly: u8,
+    line_cycles: usize,
+    // the pixels of the frame being drawn, see `pallete_to_rgba`
+    frame: Vec<u16>,
+    // logs each instruction before it runs
+    pub tracer: Option<Tracer>,
+    // counts where the time goes, written out when the gameboy is dropped
+    pub profiler: Option<Profiler>,
+    // how long it has been running in cycles at normal speed
+    cycles: u64,
+    // the link cable and how far into the current slice we are, see `link::SLICE`
+    link: Option<Box<dyn Cable>>,
+    link_cycles: u32,
+}
+
+impl GameBoy {
+    pub fn new(rom: Vec<u8>) -> Self {
+        let memory = Rc::new(RefCell::new(Memory::new(rom)));
+        let mut cpu = Cpu::new(memory.clone());
+        if memory.borrow().is_cgb() {
+            cpu.regs = crate::registers::CpuRegisters::post_boot_cgb();
+        } else if memory.borrow().sgb.is_some() {
+            cpu.regs = crate::registers::CpuRegisters::post_boot_sgb();
+        }
+        let ppu = Ppu::new(memory.clone());
+        Self {
+            memory,
+            cpu,
+            ppu,
+            ly: 0,
+            line_cycles: 0,
+            frame: Vec::with_capacity((SCREEN_WIDTH * SCREEN_HEIGHT) as usize),
+            tracer: None,
+            profiler: None,
+            cycles: 0,
+            link: None,
+            link_cycles: 0,
+        }
+    }
+
+    /// runs a single instruction and keeps the scanlines in step with it.
+    /// returns true once the last scanline of a frame has been finished
+    pub fn step(&mut self) -> bool {
+        self.update_ly();
+        // a dma which ran past the end of the last frame still has the cpu halted
+        if !self.memory.borrow().dma_stalled() && self.run_instruction() {
+            return true;
+        }
+        // cgb vram dma halts the cpu until it is done
+        loop {
+            let stall = self.memory.borrow_mut().take_dma_stall(4);
+            if stall == 0 {
+                return false;
+            }
+            self.update_ly();
+            if self.advance(stall) {
+                return true;
+            }
+        }
+    }
+
+    fn update_ly(&self) {
+        let mut memory = self.memory.borrow_mut();
+        // update the ly value for the ppu, lines dont always start on cycle 0
+        if memory.peek(io::LY) != self.ly {
+            memory.unchecked_write(io::LY, self.ly);
+        }
+        let mode = self.ppu_mode(memory.peek(0xFF40) & 0b1000_0000 != 0);
+        memory.ppu_mode = mode;
+        // the mode and the LY=LYC coincidence flag are the ppu's part of STAT,
+        // games wait on them before touching vram and oam
+        let coincidence = (memory.peek(io::LY) == memory.peek(0xFF45)) as u8;
+        let stat = memory.peek(0xFF41) & !0b0000_0111;
+        memory.unchecked_write(0xFF41, stat | coincidence << 2 | mode);
+    }
+
+    /// 2 while searching oam, 3 while drawing, 0 in hblank and 1 in vblank.
+    /// with the lcd off the ppu leaves everything alone
+    fn ppu_mode(&self, lcd_on: bool) -> u8 {
+        match self.line_cycles {
+            _ if !lcd_on => 0,
+            _ if self.ly as u32 >= SCREEN_HEIGHT => 1,
+            0..80 => 2,
+            80..HBLANK_START => 3,
+            _ => 0,
+        }
+    }
+
+    fn run_instruction(&mut self) -> bool {
+        if let Some(tracer) = &mut self.tracer {
+            // interrupts being dispatched arent instructions
+            if !self.cpu.interrupt_pending() {
+                tracer.log(&self.cpu, &self.memory.borrow());
+            }
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.before_step(&self.cpu, &self.memory.borrow());
+        }
+        let new_cycles = self.cpu.process_next();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.after_step(&self.cpu, &self.memory.borrow(), new_cycles);
+        }
+        self.advance(new_cycles)
+    }
+
+    /// moves the timers and screen on by some cpu cycles,
+    /// returns true once the last scanline of a frame has been finished
+    fn advance(&mut self, cycles: u8) -> bool {
+        self.memory.borrow_mut().tick(cycles);
+        // at double speed the cpu and timers run twice as fast as the screen
+        let ppu_cycles = if self.memory.borrow().double_speed() { cycles / 2 } else { cycles };
+        self.cycles += ppu_cycles as u64;
+        self.link_cycles += ppu_cycles as u32;
+        if self.link_cycles >= link::SLICE {
+            self.link_cycles -= link::SLICE;
+            self.sync_link();
+        }
+        let before = self.line_cycles;
+        self.line_cycles += ppu_cycles as usize;
+        let lcd_on = self.memory.borrow().peek(0xFF40) & 0b1000_0000 != 0;
+        if lcd_on && (self.ly as u32) < SCREEN_HEIGHT && before < HBLANK_START && self.line_cycles >= HBLANK_START {
+            self.memory.borrow_mut().hblank_started();
+        }
+        if self.line_cycles < MAXCYCLES {
+            return false;
+        }
+
+        // get the new scanline ready, anything past the end of this one is
+        // kept so frames average out to exactly CYCLES_PER_FRAME
+        self.line_cycles -= MAXCYCLES;
+        let mut scanline = self.ppu.draw_scanline();
+        // the lines past 144 are vblank so they never reach the screen
+        if (self.ly as u32) < SCREEN_HEIGHT {
+            self.frame.append(&mut scanline);
+        }
+        self.ly += 1;
+        // the vblank interrupt, which is how most games know when to update the screen
+        if lcd_on && self.ly as u32 == SCREEN_HEIGHT {
+            let mut memory = self.memory.borrow_mut();
+            let flags = memory.peek(io::IF);
+            memory.unchecked_write(io::IF, flags | 0b0000_0001);
+        }
+        if (self.ly as usize) < SCANLINES {
+            return false;
+        }
+        self.ly = 0;
+        // gameshark codes are written once a frame
+        let mut memory = self.memory.borrow_mut();
+        for (address, value) in memory.cheats.ram_writes() {
+            memory.unchecked_write(address, value);
+        }
+        memory.sgb_frame();
+        true
+    }
+
+    /// swaps messages with the other end of the cable, a broken cable is unplugged
+    fn sync_link(&mut self) {
+        let Some(cable) = &mut self.link else { return };
+        let message = self.memory.borrow_mut().serial.take_outgoing();
+        match cable.sync(message) {
+            Ok(message) => self.memory.borrow_mut().link_receive(message),
+            Err(e) => {
+                println!("{e}");
+                self.link = None;
+                self.memory.borrow_mut().serial.connected = false;
+            }
+        }
+    }
+
+    /// connects the serial port to another gameboy, see `link`
+    pub fn plug_in(&mut self, cable: Box<dyn Cable>) {
+        self.link = Some(cable);
+        self.link_cycles = 0;
+        self.memory.borrow_mut().serial.connected = true;
+    }
+
+    /// takes the cable back out, dropping it finishes anything a printer was printing
+    pub fn unplug(&mut self) -> Option<Box<dyn Cable>> {
+        self.memory.borrow_mut().serial.connected = false;
+        self.link.take()
+    }
+
+    /// how long it has been running in cycles at normal speed
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// the scanline currently being processed
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    /// runs until a whole frame is ready and returns it. sgb games give
+    /// the whole bordered picture, see `screen_size`
+    pub fn run_frame(&mut self) -> Framebuffer {
+        while !self.step() {}
+        self.take_frame()
+    }
+
+    /// the frame `step` just finished, see `run_frame`
+    pub fn take_frame(&mut self) -> Framebuffer {
+        let frame = std::mem::take(&mut self.frame);
+        self.frame.reserve((SCREEN_WIDTH * SCREEN_HEIGHT) as usize);
+        let (width, height) = self.screen_size();
+        let pixels = match &mut self.memory.borrow_mut().sgb {
+            Some(sgb) => sgb.render(&frame),
+            None => frame,
+        };
+        Framebuffer::new(width, height, pixels)
+    }
+
+    /// how big the frames from `run_frame` are
+    pub fn screen_size(&self) -> (u32, u32) {
+        match self.memory.borrow().sgb {
+            Some(_) => (SGB_WIDTH, SGB_HEIGHT),
+            None => (SCREEN_WIDTH, SCREEN_HEIGHT),
+        }
+    }
+
+    /// the held buttons stay the same until this is called again
+    pub fn set_buttons(&mut self, buttons: u8) {
+        self.memory.borrow_mut().set_buttons(buttons);
+    }
+
+    /// the title from the cartridge header, used to make sure save states
+    /// are only loaded into the game they came from
+    pub fn title(&self) -> Vec<u8> {
+        self.memory.borrow().memory[0x134..0x144].to_vec()
+    }
+
+    /// snapshots the entire machine into the save state format
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new(&self.title());
+        self.cpu.save_state(&mut writer);
+        self.memory.borrow().save_state(&mut writer);
+        // the ppu only holds onto data for the scanline it is drawing,
+        // its progress through the frame is tracked here instead
+        writer.u8(self.ly);
+        writer.u32(self.line_cycles as u32);
+        let frame: Vec<u8> = self.frame.iter().flat_map(|p| p.to_le_bytes()).collect();
+        writer.bytes(&frame);
+        writer.u64(self.cycles);
+        writer.finish()
+    }
+
+    /// a state which doesnt load leaves the machine exactly as it was, the
+    /// sections are read straight into it so anything loaded is put back
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let snapshot = self.save_state();
+        let loaded = self.load_sections(data);
+        if loaded.is_err() {
+            self.load_sections(&snapshot).expect("could not restore the state from before loading");
+        }
+        loaded
+    }
+    fn load_sections(&mut self, data: &[u8]) -> Result<(), String> {
+        let title = self.title();
+        let mut reader = StateReader::new(data, &title)?;
+        self.cpu.load_state(&mut reader)?;
+        self.memory.borrow_mut().load_state(&mut reader)?;
+        self.ly = reader.u8()?;
+        self.line_cycles = reader.u32()? as usize;
+        if self.ly as usize >= SCANLINES || self.line_cycles >= MAXCYCLES {
+            return Err("save state is past the end of the frame".to_string());
+        }
+        let frame = reader.bytes()?;
+        // version 1 stored a byte per pixel, before there were cgb colours
+        self.frame = match reader.version {
+            1 => frame.iter().map(|p| *p as u16).collect(),
+            _ if frame.len() % 2 != 0 => return Err("save state frame is the wrong size".to_string()),
+            _ => frame.chunks_exact(2).map(|p| u16::from_le_bytes([p[0], p[1]])).collect(),
+        };
+        // the lines drawn so far, or a whole frame nothing has taken yet
+        let lines = (self.ly as u32).min(SCREEN_HEIGHT);
+        let whole = self.ly == 0 && self.frame.len() == (SCREEN_WIDTH * SCREEN_HEIGHT) as usize;
+        if self.frame.len() != (lines * SCREEN_WIDTH) as usize && !whole {
+            return Err("save state frame is the wrong size".to_string());
+        }
+        // version 6 was from before the cycle count was saved, it carries on from where it was
+        if reader.version >= 7 {
+            self.cycles = reader.u64()?;
+        }
+        reader.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a rom of nothing but nops, the header is all zeroes so it runs as a dmg
+    fn gameboy() -> GameBoy {
+        GameBoy::new(vec![0; 0x8000])
+    }
+
+    #[test]
+    fn bad_states_are_rejected_and_rolled_back() {
+        let mut gb = gameboy();
+        gb.run_frame();
+        while gb.ly() != 10 {
+            gb.step();
+        }
+        let before = gb.save_state();
+
+        let mut edited = gameboy();
+        edited.ly = 200;
+        assert!(gb.load_state(&edited.save_state()).is_err());
+        edited.ly = 10;
+        edited.frame = vec![0; 3];
+        assert!(gb.load_state(&edited.save_state()).is_err());
+        edited.frame = vec![0; SCREEN_WIDTH as usize * 9];
+        assert!(gb.load_state(&edited.save_state()).is_err());
+        edited.line_cycles = MAXCYCLES;
+        assert!(gb.load_state(&edited.save_state()).is_err());
+        assert!(gb.save_state() == before);
+
+        edited.frame = vec![0; SCREEN_WIDTH as usize * 10];
+        edited.line_cycles = 0;
+        gb.load_state(&edited.save_state()).unwrap();
+        assert_eq!(gb.run_frame().pixels.len(), (SCREEN_WIDTH * SCREEN_HEIGHT) as usize);
+    }
+}
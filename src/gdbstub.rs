@@ -0,0 +1,340 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::args::Args;
+use crate::debugger::{Access, Watchpoint};
+use crate::gameboy::GameBoy;
+
+/// a gdb remote serial protocol server so external debuggers can attach.
+///
+/// gdb doesnt know about the SM83 so the registers are described to it in
+/// `TARGET_XML`, as six 16 bit registers: af, bc, de, hl, sp and pc.
+/// connect with `target remote localhost:<port>` after loading the xml
+/// or letting gdb ask for it.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.core">
+    <reg name="af" bitsize="16" type="uint16"/>
+    <reg name="bc" bitsize="16" type="uint16"/>
+    <reg name="de" bitsize="16" type="uint16"/>
+    <reg name="hl" bitsize="16" type="uint16"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>"#;
+
+// how many instructions are ran between checking if gdb wants to interrupt
+const INTERRUPT_CHECK: u32 = 10_000;
+
+// signal numbers sent back in stop replies
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+pub struct GdbStub {
+    gameboy: GameBoy,
+    stream: TcpStream,
+    breakpoints: Vec<u16>,
+}
+
+impl GdbStub {
+    pub fn new(gameboy: GameBoy, stream: TcpStream) -> Self {
+        Self { gameboy, stream, breakpoints: Vec::new() }
+    }
+
+    /// handles packets until gdb detaches or the connection drops
+    pub fn serve(&mut self) {
+        loop {
+            let packet = match self.read_packet() {
+                None => return,
+                Some(p) => p,
+            };
+            let reply = match packet.first() {
+                Some(b'D') => {
+                    self.send("OK");
+                    return;
+                }
+                Some(b'k') => return,
+                _ => self.handle(&packet),
+            };
+            if !self.send(&reply) {
+                return;
+            }
+        }
+    }
+
+    fn handle(&mut self, packet: &[u8]) -> String {
+        let text = String::from_utf8_lossy(packet).to_string();
+        if text.is_empty() || !text.is_char_boundary(1) {
+            return String::new();
+        }
+        let (command, body) = text.split_at(1);
+        match command {
+            "?" => format!("S{SIGTRAP:02x}"),
+            "g" => {
+                let regs = &self.gameboy.cpu.regs;
+                [regs.af(), regs.bc(), regs.de(), regs.hl(), regs.sp, regs.pc]
+                    .iter().map(|r| hex_u16_le(*r)).collect()
+            }
+            "G" => {
+                let values: Vec<u16> = (0..6).filter_map(|i| body.get(i*4..i*4+4).and_then(parse_u16_le)).collect();
+                if values.len() != 6 {
+                    return "E01".to_string();
+                }
+                for (i, value) in values.iter().enumerate() {
+                    self.set_register(i, *value);
+                }
+                "OK".to_string()
+            }
+            "p" => match usize::from_str_radix(body, 16) {
+                Ok(i) if i < 6 => {
+                    let regs = &self.gameboy.cpu.regs;
+                    hex_u16_le([regs.af(), regs.bc(), regs.de(), regs.hl(), regs.sp, regs.pc][i])
+                }
+                _ => "E01".to_string(),
+            },
+            "P" => {
+                let Some((index, value)) = body.split_once('=') else { return "E01".to_string() };
+                match (usize::from_str_radix(index, 16), parse_u16_le(value)) {
+                    (Ok(i), Some(v)) if i < 6 => {
+                        self.set_register(i, v);
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            "m" => {
+                let Some((address, len)) = parse_range(body) else { return "E01".to_string() };
+                let memory = self.gameboy.memory.borrow();
+                (0..len).map(|i| format!("{:02x}", memory.peek(address.wrapping_add(i)))).collect()
+            }
+            "M" => {
+                let Some((range, data)) = body.split_once(':') else { return "E01".to_string() };
+                let Some((address, len)) = parse_range(range) else { return "E01".to_string() };
+                let mut memory = self.gameboy.memory.borrow_mut();
+                for i in 0..len {
+                    let Some(byte) = data.get(i as usize*2..i as usize*2+2).and_then(|b| u8::from_str_radix(b, 16).ok()) else {
+                        return "E01".to_string();
+                    };
+                    memory.unchecked_write(address.wrapping_add(i), byte);
+                }
+                "OK".to_string()
+            }
+            "c" => {
+                self.resume_at(body);
+                self.run(false)
+            }
+            "s" => {
+                self.resume_at(body);
+                self.run(true)
+            }
+            "Z" | "z" => self.breakpoint(command == "Z", body),
+            "H" => "OK".to_string(),
+            "q" => self.query(body),
+            // anything not supported gets an empty reply
+            _ => String::new(),
+        }
+    }
+
+    fn query(&self, body: &str) -> String {
+        if body.starts_with("Supported") {
+            return "PacketSize=4000;qXfer:features:read+".to_string();
+        }
+        if let Some(request) = body.strip_prefix("Xfer:features:read:target.xml:") {
+            // the xml is sent in pieces, the first letter says if there is more
+            let Some((offset, len)) = request.split_once(',') else { return "E01".to_string() };
+            let (Ok(offset), Ok(len)) = (usize::from_str_radix(offset, 16), usize::from_str_radix(len, 16)) else {
+                return "E01".to_string();
+            };
+            let offset = offset.min(TARGET_XML.len());
+            let end = (offset + len).min(TARGET_XML.len());
+            let more = if end < TARGET_XML.len() { "m" } else { "l" };
+            return format!("{more}{}", &TARGET_XML[offset..end]);
+        }
+        match body {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn set_register(&mut self, index: usize, value: u16) {
+        let regs = &mut self.gameboy.cpu.regs;
+        match index {
+            0 => regs.set_af(value),
+            1 => regs.set_bc(value),
+            2 => regs.set_de(value),
+            3 => regs.set_hl(value),
+            4 => regs.sp = value,
+            _ => regs.pc = value,
+        }
+    }
+
+    /// `c` and `s` can be given an address to carry on from
+    fn resume_at(&mut self, body: &str) {
+        if let Ok(address) = u16::from_str_radix(body, 16) {
+            self.gameboy.cpu.regs.pc = address;
+        }
+    }
+
+    /// `Z<type>,<addr>,<kind>` adds and `z` removes. types 0 and 1 are breakpoints,
+    /// 2 is a write watchpoint, 3 a read watchpoint and 4 an access watchpoint
+    fn breakpoint(&mut self, insert: bool, body: &str) -> String {
+        let parts: Vec<&str> = body.split(',').collect();
+        let (Some(kind), Some(address)) = (parts.first(), parts.get(1)) else { return "E01".to_string() };
+        let Ok(address) = u16::from_str_radix(address, 16) else { return "E01".to_string() };
+        // watchpoints can cover more than one byte
+        let len = parts.get(2).and_then(|l| u16::from_str_radix(l, 16).ok()).unwrap_or(1).max(1);
+
+        let (read, write) = match *kind {
+            "0" | "1" => {
+                if insert {
+                    self.breakpoints.push(address);
+                } else {
+                    self.breakpoints.retain(|b| *b != address);
+                }
+                return "OK".to_string();
+            }
+            "2" => (false, true),
+            "3" => (true, false),
+            "4" => (true, true),
+            _ => return String::new(),
+        };
+        let watchpoint = Watchpoint { start: address, end: address.saturating_add(len - 1), read, write };
+        let mut memory = self.gameboy.memory.borrow_mut();
+        if insert {
+            memory.watchpoints.push(watchpoint);
+        } else {
+            memory.watchpoints.retain(|w| !(w.start == watchpoint.start && w.end == watchpoint.end && w.read == read && w.write == write));
+        }
+        "OK".to_string()
+    }
+
+    /// runs until something stops it and returns the stop reply
+    fn run(&mut self, single_step: bool) -> String {
+        let mut steps = 0;
+        loop {
+            self.gameboy.step();
+            if let Some(hit) = self.gameboy.memory.borrow().take_watch_hit() {
+                let kind = match hit.access {
+                    Access::Read => "rwatch",
+                    Access::Write => "watch",
+                };
+                return format!("T{SIGTRAP:02x}{kind}:{:04x};", hit.address);
+            }
+            if single_step || self.breakpoints.contains(&self.gameboy.cpu.regs.pc) {
+                return format!("S{SIGTRAP:02x}");
+            }
+            steps += 1;
+            if steps % INTERRUPT_CHECK == 0 && self.interrupt_requested() {
+                return format!("S{SIGINT:02x}");
+            }
+        }
+    }
+
+    /// gdb sends a single 0x03 byte when the user presses ctrl-c
+    fn interrupt_requested(&mut self) -> bool {
+        self.stream.set_nonblocking(true).unwrap();
+        let mut byte = [0];
+        let interrupted = matches!(self.stream.read(&mut byte), Ok(1) if byte[0] == 0x03);
+        self.stream.set_nonblocking(false).unwrap();
+        interrupted
+    }
+
+    /// packets look like `$<data>#<checksum>`, everything outside of them
+    /// (acks and interrupts) is skipped. returns none once the connection closes
+    fn read_packet(&mut self) -> Option<Vec<u8>> {
+        let mut byte = [0];
+        loop {
+            self.stream.read_exact(&mut byte).ok()?;
+            if byte[0] != b'$' {
+                continue;
+            }
+            let mut data = Vec::new();
+            loop {
+                self.stream.read_exact(&mut byte).ok()?;
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum).ok()?;
+            let expected = u8::from_str_radix(&String::from_utf8_lossy(&checksum), 16).ok();
+            if expected != Some(checksum_of(&data)) {
+                self.stream.write_all(b"-").ok()?;
+                continue;
+            }
+            self.stream.write_all(b"+").ok()?;
+            return Some(unescape(&data));
+        }
+    }
+
+    fn send(&mut self, reply: &str) -> bool {
+        let packet = format!("${reply}#{:02x}", checksum_of(reply.as_bytes()));
+        self.stream.write_all(packet.as_bytes()).is_ok()
+    }
+}
+
+/// waits for gdb to connect on the port and then serves it
+pub fn run(args: &Args, port: u16) {
+    let mut gameboy = GameBoy::new(args.rom());
+    gameboy.tracer = args.tracer();
+    gameboy.profiler = args.profiler();
+    gameboy.memory.borrow_mut().cheats = args.cheats();
+    gameboy.memory.borrow_mut().lax_access = args.lax_access;
+    gameboy.memory.borrow_mut().doctor_ly = args.trace_doctor;
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Err(e) => panic!("could not listen on port {port}: {e}"),
+        Ok(l) => l,
+    };
+    println!("waiting for gdb on localhost:{port}");
+    let stream = match listener.accept() {
+        Err(e) => panic!("gdb could not connect: {e}"),
+        Ok((s, _)) => s,
+    };
+    stream.set_nodelay(true).ok();
+    println!("gdb connected");
+    GdbStub::new(gameboy, stream).serve();
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+/// `}` escapes the next byte, which has been xor'd with 0x20
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(b) = bytes.next() {
+        match (*b, bytes.clone().next()) {
+            (b'}', Some(next)) => {
+                out.push(next ^ 0x20);
+                bytes.next();
+            }
+            _ => out.push(*b),
+        }
+    }
+    out
+}
+
+/// registers go over the wire in target byte order, which is little endian
+fn hex_u16_le(value: u16) -> String {
+    format!("{:02x}{:02x}", value & 0xFF, value >> 8)
+}
+fn parse_u16_le(text: &str) -> Option<u16> {
+    let lsb = u8::from_str_radix(text.get(0..2)?, 16).ok()?;
+    let msb = u8::from_str_radix(text.get(2..4)?, 16).ok()?;
+    Some(crate::combine_u8s(lsb, msb))
+}
+/// `<addr>,<len>`, both in hex
+fn parse_range(text: &str) -> Option<(u16, u16)> {
+    let (address, len) = text.split_once(',')?;
+    let address = u32::from_str_radix(address, 16).ok()?;
+    let len = u32::from_str_radix(len, 16).ok()?;
+    if address > 0xFFFF || len > 0xFFFF {
+        return None;
+    }
+    Some((address as u16, len as u16))
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+// codes never get longer than this, the table starts over once it is full
+const MAX_CODE_SIZE: u8 = 12;
+const MAX_CODES: u16 = 4095;
+
+/// an animated gif, written out a frame at a time from RGBA bytes.
+///
+/// every frame gets its own colour table. the dmg only ever has 4 colours
+/// and a cgb frame rarely has more than 256, when it does the colours are
+/// made coarser until they fit
+pub struct Gif<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+}
+
+impl<W: Write> Gif<W> {
+    pub fn new(mut out: W, width: u16, height: u16) -> std::io::Result<Self> {
+        let mut head = b"GIF89a".to_vec();
+        head.extend(width.to_le_bytes());
+        head.extend(height.to_le_bytes());
+        // no global colour table, background colour and aspect ratio
+        head.extend([0, 0, 0]);
+        // loops forever
+        head.extend([0x21, 0xFF, 0x0B]);
+        head.extend(b"NETSCAPE2.0");
+        head.extend([0x03, 0x01, 0x00, 0x00, 0x00]);
+        out.write_all(&head)?;
+        Ok(Self { out, width, height })
+    }
+
+    /// shows the frame for `delay` hundredths of a second
+    pub fn frame(&mut self, rgba: &[u8], delay: u16) -> std::io::Result<()> {
+        if rgba.len() != self.width as usize * self.height as usize * 4 {
+            panic!("frame doesnt match the animation's size");
+        }
+        let (colours, indices) = palette(rgba);
+        // the table is always a power of 2 long, at least 4 for the lzw codes
+        let bits = (colours.len().next_power_of_two().trailing_zeros() as u8).max(2);
+
+        let mut out = Vec::new();
+        // graphic control: the frame stays up until the next one replaces it
+        out.extend([0x21, 0xF9, 0x04, 0b0000_0100]);
+        out.extend(delay.to_le_bytes());
+        out.extend([0x00, 0x00]);
+        // the image covers the whole screen and has a local colour table
+        out.push(0x2C);
+        out.extend([0, 0, 0, 0]);
+        out.extend(self.width.to_le_bytes());
+        out.extend(self.height.to_le_bytes());
+        out.push(0x80 | (bits - 1));
+        for i in 0..1 << bits {
+            out.extend(colours.get(i).unwrap_or(&[0, 0, 0]));
+        }
+        out.push(bits);
+        // the data is split into blocks of up to 255 bytes
+        for block in lzw(&indices, bits).chunks(255) {
+            out.push(block.len() as u8);
+            out.extend(block);
+        }
+        out.push(0);
+        self.out.write_all(&out)
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// the frame's colours and each pixel's index into them. each round that
+/// finds more than 256 drops another bit from every channel
+fn palette(rgba: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut shift = 0;
+    'coarser: loop {
+        let mut lookup: HashMap<[u8; 3], u8> = HashMap::new();
+        let mut colours = Vec::new();
+        let mut indices = Vec::with_capacity(rgba.len() / 4);
+        for pixel in rgba.chunks_exact(4) {
+            let colour = [pixel[0], pixel[1], pixel[2]].map(|c| c >> shift << shift);
+            let index = match lookup.get(&colour) {
+                Some(i) => *i,
+                // the table is full, so start again with fewer colours
+                None if colours.len() == 256 => {
+                    shift += 1;
+                    continue 'coarser;
+                }
+                None => {
+                    let index = colours.len() as u8;
+                    lookup.insert(colour, index);
+                    colours.push(colour);
+                    index
+                }
+            };
+            indices.push(index);
+        }
+        return (colours, indices);
+    }
+}
+
+/// codes are packed lowest bit first
+struct CodeWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u8,
+    size: u8,
+}
+impl CodeWriter {
+    /// the code after the one which uses up the last code of this size is a bit longer
+    fn code(&mut self, code: u16, next: u16) {
+        self.bits |= (code as u32) << self.count;
+        self.count += self.size;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+        if next >= 1 << self.size && self.size < MAX_CODE_SIZE {
+            self.size += 1;
+        }
+    }
+}
+
+/// gif's variable length lzw, the same way giflib does it
+fn lzw(indices: &[u8], min_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_size;
+    let end = clear + 1;
+    let mut writer = CodeWriter { out: Vec::new(), bits: 0, count: 0, size: min_size + 1 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    writer.code(clear, next);
+
+    let mut current = None;
+    for index in indices {
+        let Some(prefix) = current else {
+            current = Some(*index as u16);
+            continue;
+        };
+        if let Some(code) = table.get(&(prefix, *index)) {
+            current = Some(*code);
+            continue;
+        }
+        writer.code(prefix, next);
+        if next >= MAX_CODES {
+            writer.code(clear, next);
+            table.clear();
+            next = end + 1;
+            writer.size = min_size + 1;
+        } else {
+            table.insert((prefix, *index), next);
+            next += 1;
+        }
+        current = Some(*index as u16);
+    }
+    if let Some(code) = current {
+        writer.code(code, next);
+    }
+    writer.code(end, next);
+    if writer.count > 0 {
+        writer.out.push(writer.bits as u8);
+    }
+    writer.out
+}
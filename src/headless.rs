@@ -0,0 +1,248 @@
+use std::fs;
+
+use crate::args::Args;
+use crate::gameboy::GameBoy;
+use crate::framebuffer::Framebuffer;
+use crate::movie::Movie;
+use crate::link;
+use crate::pacing::{CYCLES_PER_FRAME, CLOCK_SPEED};
+use crate::{pallete_to_rgba, png, viewers};
+
+/// runs the rom without a window for a set number of frames, or
+/// for as long as the movie being played back lasts.
+///
+/// this is mostly for golden image testing, the final frame is checked
+/// against a hash and/or a reference png. if either doesnt match the
+/// process exits with a failure so it can be used from scripts.
+pub fn run(args: &Args) {
+    let rom = args.rom();
+    let mut gameboy = GameBoy::new(rom.clone());
+    gameboy.tracer = args.tracer();
+    gameboy.profiler = args.profiler();
+    gameboy.memory.borrow_mut().cheats = args.cheats();
+    gameboy.memory.borrow_mut().lax_access = args.lax_access;
+    gameboy.memory.borrow_mut().doctor_ly = args.trace_doctor;
+    // the other gameboy on the link cable just runs alongside, only its hash is shown
+    let mut partner = args.link_gameboy();
+    match &mut partner {
+        Some(other) => link::connect(&mut gameboy, other),
+        None => if let Some(cable) = args.cable() {
+            gameboy.plug_in(cable);
+        },
+    }
+    let mut partner_frame = Framebuffer::default();
+    let mut run_frame = |gameboy: &mut GameBoy| match &mut partner {
+        Some(other) => {
+            let (frame, other_frame) = link::run_linked_frame(gameboy, other);
+            partner_frame = other_frame;
+            frame
+        }
+        None => gameboy.run_frame(),
+    };
+    let size = gameboy.screen_size();
+    let mut recorder = args.recorder();
+    let (first, last) = args.video_frames.unwrap_or((1, u32::MAX));
+    let mut dumper = args.dumper();
+    let mut record = |frame: &Framebuffer, number: u32| {
+        if let Some(recorder) = &mut recorder {
+            if (first..=last).contains(&number) {
+                if let Err(e) = recorder.frame(frame) {
+                    panic!("{e}");
+                }
+            }
+        }
+        // there is no sound hardware to take samples from yet
+        if let Some(dumper) = &mut dumper {
+            if let Err(e) = dumper.frame(frame, &[]) {
+                panic!("{e}");
+            }
+        }
+    };
+    let mut frame = Framebuffer::default();
+    let mut frames = 0;
+    match &args.movie {
+        Some(path) => {
+            let movie = load_movie(path, &rom);
+            if let Err(e) = gameboy.load_state(&movie.start_state) {
+                panic!("could not load the movie's starting state: {e}");
+            }
+            // it has to run the way it did when it was recorded
+            gameboy.memory.borrow_mut().lax_access = movie.lax_access;
+            for buttons in &movie.inputs {
+                gameboy.set_buttons(*buttons);
+                frame = run_frame(&mut gameboy);
+                frames += 1;
+                record(&frame, frames);
+            }
+        }
+        None => {
+            for _ in 0..args.frames {
+                frame = run_frame(&mut gameboy);
+                frames += 1;
+                record(&frame, frames);
+            }
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        let path = recorder.path().to_string();
+        match recorder.finish() {
+            Err(e) => println!("{e}"),
+            Ok(n) => println!("recorded {n} frames to {path}"),
+        }
+    }
+    if let Some(dumper) = dumper {
+        match dumper.finish() {
+            Err(e) => println!("{e}"),
+            Ok(n) => println!("dumped {n} frames, {}", dump_hint(args, size)),
+        }
+    }
+    if let Some(path) = &args.screenshot {
+        match frame.save_png(path, args.scale) {
+            Err(e) => println!("{e}"),
+            Ok(_) => println!("screenshot written to {path}"),
+        }
+    }
+
+    if let Some(dir) = &args.dump_vram {
+        match viewers::dump(&gameboy.memory.borrow(), dir, args.tile_palette) {
+            Err(e) => println!("{e}"),
+            Ok(_) => println!("vram written to {dir}"),
+        }
+    }
+
+    // makes sure the trace and profile are written, the process might exit below
+    drop(gameboy);
+
+    let hash = frame_hash(&frame.pixels);
+    println!("frame {frames} hash: {hash:016x}");
+    if args.link_rom.is_some() {
+        println!("linked frame {frames} hash: {:016x}", frame_hash(&partner_frame.pixels));
+    }
+
+    let mut passed = true;
+    if let Some(expected) = args.hash {
+        if expected != hash {
+            println!("hash mismatch, expected {expected:016x}");
+            passed = false;
+        }
+    }
+    if let Some(reference) = &args.reference {
+        let diff_path = match &args.diff {
+            Some(p) => p.clone(),
+            None => format!("{}-diff.png", reference.trim_end_matches(".png")),
+        };
+        passed &= compare_to_reference(&frame, reference, &diff_path);
+    }
+
+    if !passed {
+        std::process::exit(1);
+    }
+}
+
+/// how to tell ffmpeg what the dumped files are, as raw frames and pcm dont say
+fn dump_hint(args: &Args, (width, height): (u32, u32)) -> String {
+    let mut inputs = String::new();
+    if let Some(path) = &args.dump_video {
+        if !path.ends_with(".y4m") {
+            inputs += &format!("-f rawvideo -pix_fmt rgba -s {width}x{height} -framerate {CLOCK_SPEED}/{CYCLES_PER_FRAME} ");
+        }
+        inputs += &format!("-i {path} ");
+    }
+    if let Some(path) = &args.dump_audio {
+        if !path.ends_with(".wav") {
+            inputs += &format!("-f s16le -ar {} -ac 2 ", args.audio_rate);
+        }
+        inputs += &format!("-i {path} ");
+    }
+    let silent = if args.dump_audio.is_some() { "the audio is silent as there is no sound emulation yet. " } else { "" };
+    format!("{silent}encode with: ffmpeg {inputs}out.mp4")
+}
+
+fn load_movie(path: &str, rom: &[u8]) -> Movie {
+    let data = match fs::read(path) {
+        Err(_) => panic!("invalid movie file provided"),
+        Ok(f) => f,
+    };
+    let movie = match Movie::from_bytes(&data) {
+        Err(e) => panic!("could not read movie: {e}"),
+        Ok(m) => m,
+    };
+    if let Err(e) = movie.check_rom(rom) {
+        panic!("{e}");
+    }
+    movie
+}
+
+/// FNV-1a over the pixels. It only has to notice when a frame changes,
+/// dmg shades are hashed the same as when they were stored as bytes
+pub fn frame_hash(frame: &[u16]) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for pixel in frame {
+        hash ^= *pixel as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01B3);
+    }
+    hash
+}
+
+/// reference images may come from emulators with other colours so
+/// each pixel is matched to whichever of our shades is closest in brightness
+fn closest_shade(pixel: &[u8]) -> u16 {
+    let brightness = |r: u8, g: u8, b: u8| (r as i32 * 299 + g as i32 * 587 + b as i32 * 114) / 1000;
+    let target = brightness(pixel[0], pixel[1], pixel[2]);
+    (0..4).min_by_key(|i| {
+        let (r, g, b) = pallete_to_rgba(*i);
+        (brightness(r, g, b) - target).abs()
+    }).unwrap()
+}
+
+/// cgb colours can only be a little off, since other emulators round
+/// RGB555 differently when they turn it into 8 bits
+fn pixel_matches(pixel: u16, expected: &[u8]) -> bool {
+    if pixel & 0x8000 == 0 {
+        return closest_shade(expected) == pixel;
+    }
+    let (r, g, b) = pallete_to_rgba(pixel);
+    [r, g, b].iter().zip(expected).all(|(a, b)| a.abs_diff(*b) <= 8)
+}
+
+/// returns whether the frame matched. a diff image is written when it doesnt,
+/// matching pixels are faded out and the mismatched ones are drawn in red.
+fn compare_to_reference(frame: &Framebuffer, reference_path: &str, diff_path: &str) -> bool {
+    let (width, height) = (frame.width, frame.height);
+    let reference = match fs::read(reference_path) {
+        Err(_) => panic!("invalid reference file provided"),
+        Ok(f) => f,
+    };
+    let reference = match png::decode(&reference) {
+        Err(e) => panic!("could not decode reference image: {e}"),
+        Ok(i) => i,
+    };
+    if reference.width != width || reference.height != height {
+        println!("reference image is {}x{}, expected {width}x{height}", reference.width, reference.height);
+        return false;
+    }
+
+    let mut mismatches = 0;
+    let mut diff = Vec::with_capacity(frame.pixels.len() * 4);
+    for (index, expected) in frame.pixels.iter().zip(reference.rgba.chunks_exact(4)) {
+        if pixel_matches(*index, expected) {
+            let (r, g, b) = pallete_to_rgba(*index);
+            diff.extend([r / 4, g / 4, b / 4, 255]);
+        } else {
+            mismatches += 1;
+            diff.extend([0xFF, 0x00, 0x00, 0xFF]);
+        }
+    }
+    if mismatches == 0 {
+        println!("frame matches {reference_path}");
+        return true;
+    }
+
+    println!("{mismatches} pixels differ from {reference_path}, diff written to {diff_path}");
+    let image = png::encode(width, height, &diff);
+    if fs::write(diff_path, image).is_err() {
+        println!("could not write the diff image");
+    }
+    false
+}
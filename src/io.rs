@@ -0,0 +1,100 @@
+use crate::cgb;
+
+pub const P1: u16 = 0xFF00;
+pub const DIV: u16 = 0xFF04;
+pub const IF: u16 = 0xFF0F;
+pub const LY: u16 = 0xFF44;
+pub const DMA: u16 = 0xFF46;
+
+/// how the cpu sees an io register. bits in `unused` always read as 1 and
+/// only bits in `writable` can be changed, the rest are left to the emulator
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Register {
+    pub unused: u8,
+    pub writable: u8,
+}
+
+const fn reg(unused: u8, writable: u8) -> Register {
+    Register { unused, writable }
+}
+
+// a plain byte of memory
+const PLAIN: Register = reg(0x00, 0xFF);
+// nothing is there, it reads as 0xFF and ignores writes
+const UNMAPPED: Register = reg(0xFF, 0x00);
+// can be written but reads as 0xFF
+const WRITE_ONLY: Register = reg(0xFF, 0xFF);
+// only the emulator changes it
+const READ_ONLY: Register = reg(0x00, 0x00);
+
+/// the register at an address in 0xFF00-0xFF7F. the cgb has registers
+/// in some of the gaps which are unmapped on the dmg
+pub fn register(address: u16, is_cgb: bool) -> Register {
+    match address {
+        // the buttons are read through the selection bits, see `read_p1`
+        P1 => reg(0xC0, 0x30),
+        // serial data and control, the cgb has a fast clock bit
+        0xFF01 => PLAIN,
+        0xFF02 if is_cgb => reg(0x7C, 0x83),
+        0xFF02 => reg(0x7E, 0x81),
+        // the timers, writing anything to DIV resets it
+        DIV | 0xFF05 | 0xFF06 => PLAIN,
+        0xFF07 => reg(0xF8, 0x07),
+        IF => reg(0xE0, 0x1F),
+
+        // sound. the lengths and frequencies can only be written, so only
+        // the duty, envelope and length enable bits read back
+        0xFF10 => reg(0x80, 0x7F),
+        0xFF11 | 0xFF16 => reg(0x3F, 0xFF),
+        0xFF12 | 0xFF17 | 0xFF21 | 0xFF22 | 0xFF24 | 0xFF25 => PLAIN,
+        0xFF13 | 0xFF18 | 0xFF1B | 0xFF1D | 0xFF20 => WRITE_ONLY,
+        0xFF14 | 0xFF19 | 0xFF1E | 0xFF23 => reg(0xBF, 0xFF),
+        0xFF1A => reg(0x7F, 0x80),
+        0xFF1C => reg(0x9F, 0x60),
+        // the channels' on flags are read only
+        0xFF26 => reg(0x70, 0x80),
+        // wave ram
+        0xFF30..=0xFF3F => PLAIN,
+
+        // the ppu. the cpu only gets the interrupt enables in STAT, the mode
+        // and coincidence bits are written by `GameBoy::update_ly`
+        0xFF40 => PLAIN,
+        0xFF41 => reg(0x80, 0x78),
+        0xFF42 | 0xFF43 => PLAIN,
+        LY => READ_ONLY,
+        0xFF45 | DMA | 0xFF47..=0xFF4B => PLAIN,
+
+        // everything from here on is the cgb's, see the `cgb` module
+        cgb::KEY1 if is_cgb => reg(0x7E, 0x01),
+        cgb::VBK if is_cgb => reg(0xFE, 0x01),
+        cgb::HDMA1..=cgb::HDMA4 if is_cgb => WRITE_ONLY,
+        // the infrared port, nothing ever shines into it so bit 1 reads as 1
+        0xFF56 if is_cgb => reg(0x3E, 0xC1),
+        cgb::BCPS | cgb::OCPS if is_cgb => reg(0x40, 0xBF),
+        // object priority, by oam index or by x like the dmg
+        0xFF6C if is_cgb => reg(0xFE, 0x01),
+        cgb::SVBK if is_cgb => reg(0xF8, 0x07),
+        0xFF72..=0xFF74 if is_cgb => PLAIN,
+        0xFF75 if is_cgb => reg(0x8F, 0x70),
+        // the sound channels' current output
+        0xFF76 | 0xFF77 if is_cgb => READ_ONLY,
+        _ => UNMAPPED,
+    }
+}
+
+/// echo ram is a mirror of 0xC000-0xDDFF
+pub fn unecho(address: u16) -> u16 {
+    match address {
+        0xE000..=0xFDFF => address - 0x2000,
+        _ => address,
+    }
+}
+
+/// what 0xFEA0-0xFEFF reads as while oam is accessible. the dmg gives 0,
+/// the cgb gives the high nibble of the address twice
+pub fn unusable(address: u16, is_cgb: bool) -> u8 {
+    match is_cgb {
+        true => (address as u8 & 0xF0) | ((address as u8) >> 4),
+        false => 0x00,
+    }
+}
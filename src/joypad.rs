@@ -0,0 +1,40 @@
+/// the buttons are kept in a single byte, a set bit meaning it is held.
+/// the lower nybble are the action buttons and the upper nybble the
+/// directions, in the same order the P1 register reports them
+#[repr(u8)]
+#[derive(Clone, Copy)]
+pub enum Button {
+    A=0,
+    B=1,
+    Select=2,
+    Start=3,
+    Right=4,
+    Left=5,
+    Up=6,
+    Down=7,
+}
+
+/// sets or clears a button in the held byte
+pub fn set_button(buttons: &mut u8, button: Button, held: bool) {
+    let mask = 1 << button as u8;
+    if held {
+        *buttons |= mask;
+    } else {
+        *buttons &= !mask;
+    }
+}
+
+/// what the P1 register reads as. `select` is the value the game last wrote,
+/// bit 4 low selects the directions and bit 5 low the action buttons.
+/// a 0 in the lower nybble means the button is pressed
+pub fn read_p1(select: u8, buttons: u8) -> u8 {
+    let mut pressed = 0;
+    if select & 0b0001_0000 == 0 {
+        pressed |= buttons >> 4;
+    }
+    if select & 0b0010_0000 == 0 {
+        pressed |= buttons & 0b0000_1111;
+    }
+    // the top two bits arent connected to anything
+    0b1100_0000 | (select & 0b0011_0000) | (!pressed & 0b0000_1111)
+}
@@ -0,0 +1,94 @@
+#![allow(unused)]
+#![allow(unreachable_code)]
+#![allow(unsafe_code)]
+
+pub mod cpu;
+pub mod memory;
+pub mod registers;
+pub mod ppu;
+mod opcodes;
+pub mod gameboy;
+pub mod args;
+pub mod headless;
+pub mod png;
+pub mod savestate;
+pub mod rewind;
+pub mod joypad;
+pub mod movie;
+pub mod pacing;
+pub mod debugger;
+pub mod disasm;
+pub mod trace;
+pub mod gdbstub;
+pub mod symbols;
+pub mod callstack;
+pub mod profiler;
+pub mod viewers;
+pub mod ramsearch;
+pub mod cheats;
+pub mod patch;
+pub mod cgb;
+pub mod io;
+pub mod sgb;
+pub mod serial;
+pub mod link;
+pub mod printer;
+pub mod framebuffer;
+pub mod gif;
+pub mod capture;
+pub mod dump;
+
+/// little endian reading;
+/// 
+/// the first number parsed will be the lower byte and the 
+/// second will be the upper byte.
+pub fn combine_u8s(lsb: u8, msb: u8) -> u16 {
+    ((msb as u16) << 8) + lsb as u16
+}
+/// the upper byte is returned first. the lower byte is
+/// returned secondly.
+pub fn split_u16(a: u16) -> (u8, u8) {
+    ((a >> 8) as u8, (a & 0xFF) as u8)
+}
+
+pub fn get_rom(rom_path: &str) -> Vec<u8> {
+    match std::fs::read(rom_path) {
+        Err(_) => panic!("invalid file provided"),
+        Ok(f) => f,
+    }
+}
+/// the rom with each patch applied in turn, the files themselves are left alone
+pub fn get_patched_rom(rom_path: &str, patch_paths: &[String]) -> Vec<u8> {
+    let mut rom = get_rom(rom_path);
+    for path in patch_paths {
+        let data = match std::fs::read(path) {
+            Err(e) => panic!("could not read {path}: {e}"),
+            Ok(d) => d,
+        };
+        rom = match patch::apply(&rom, &data) {
+            Err(e) => panic!("could not apply {path}: {e}"),
+            Ok(r) => r,
+        };
+    }
+    rom
+}
+
+/// colour 0 is the lightest shade on the dmg, 3 the darkest.
+/// cgb pixels are RGB555 with the top bit set to tell them apart
+pub fn pallete_to_rgba(i: u16) -> (u8, u8, u8) {
+    if i & 0x8000 != 0 {
+        // 5 bits are stretched to 8 so that 0x1F is still full brightness
+        let channel = |shift: u16| {
+            let c = ((i >> shift) & 0x1F) as u8;
+            (c << 3) | (c >> 2)
+        };
+        return (channel(0), channel(5), channel(10));
+    }
+    match i {
+        0 => (0xFF, 0xFF, 0xFF),
+        1 => (0xA0, 0xA0, 0xA0),
+        2 => (0x50, 0x50, 0x50),
+        3 => (0x00, 0x00, 0x00),
+        _ => panic!("invalid pallete index"),
+    }
+}
\ No newline at end of file
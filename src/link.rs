@@ -0,0 +1,133 @@
+use std::{rc::Rc, cell::RefCell, collections::VecDeque};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::gameboy::GameBoy;
+use crate::framebuffer::Framebuffer;
+use crate::serial::Message;
+
+/// how often the two ends of a cable sync up, in cycles at normal speed.
+///
+/// each end sends a message every slice and acts on the one the other end
+/// sent the slice before, so they never drift apart by more than a slice.
+/// a byte takes three slices to go there and back, which is quicker than the
+/// 4096 cycles a normal speed transfer takes so those finish right on time
+pub const SLICE: u32 = 1024;
+
+/// the other end of the link cable
+pub trait Cable {
+    /// sends this slice's message and returns the other end's one from the last slice
+    fn sync(&mut self, message: Message) -> Result<Message, String>;
+}
+
+/// both gameboys in the same process, see `run_linked_frame`
+pub struct LocalCable {
+    outgoing: Rc<RefCell<VecDeque<Message>>>,
+    incoming: Rc<RefCell<VecDeque<Message>>>,
+}
+
+/// the two ends of a cable, each starts with a blank message waiting for it
+pub fn local_pair() -> (LocalCable, LocalCable) {
+    let a = Rc::new(RefCell::new(VecDeque::from([Message::default()])));
+    let b = Rc::new(RefCell::new(VecDeque::from([Message::default()])));
+    (
+        LocalCable { outgoing: a.clone(), incoming: b.clone() },
+        LocalCable { outgoing: b, incoming: a },
+    )
+}
+
+impl Cable for LocalCable {
+    fn sync(&mut self, message: Message) -> Result<Message, String> {
+        self.outgoing.borrow_mut().push_back(message);
+        Ok(self.incoming.borrow_mut().pop_front().unwrap_or_default())
+    }
+}
+
+/// the other end is another process, over tcp or a unix socket. reading
+/// blocks so whichever emulator is ahead waits for the other one
+pub struct StreamCable<S: Read + Write> {
+    stream: S,
+}
+
+impl<S: Read + Write> StreamCable<S> {
+    /// the other end is sent a blank message for it to read on its first sync
+    fn new(mut stream: S) -> Result<Self, String> {
+        stream.write_all(&Message::default().to_bytes()).map_err(|e| format!("link cable: {e}"))?;
+        Ok(Self { stream })
+    }
+}
+
+impl<S: Read + Write> Cable for StreamCable<S> {
+    fn sync(&mut self, message: Message) -> Result<Message, String> {
+        let mut bytes = [0; 3];
+        self.stream.write_all(&message.to_bytes())
+            .and_then(|_| self.stream.read_exact(&mut bytes))
+            .map_err(|e| format!("link cable unplugged: {e}"))?;
+        Ok(Message::from_bytes(bytes))
+    }
+}
+
+/// waits for another emulator to join. the address is `host:port`, or `unix:<path>` for a unix socket
+pub fn host(address: &str) -> Result<Box<dyn Cable>, String> {
+    println!("waiting for the other end of the link cable on {address}");
+    #[cfg(unix)]
+    if let Some(path) = address.strip_prefix("unix:") {
+        use std::os::unix::{fs::FileTypeExt, net::UnixListener};
+        // a socket left behind by an earlier run would stop this binding,
+        // anything else there is left alone in case the path was a typo
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(format!("could not listen on {path}: it is already there and isnt a socket"));
+            }
+            std::fs::remove_file(path).map_err(|e| format!("could not remove the old socket {path}: {e}"))?;
+        }
+        let listener = UnixListener::bind(path).map_err(|e| format!("could not listen on {path}: {e}"))?;
+        let (stream, _) = listener.accept().map_err(|e| format!("link cable: {e}"))?;
+        return Ok(Box::new(StreamCable::new(stream)?));
+    }
+    let listener = TcpListener::bind(address).map_err(|e| format!("could not listen on {address}: {e}"))?;
+    let (stream, _) = listener.accept().map_err(|e| format!("link cable: {e}"))?;
+    tcp_cable(stream)
+}
+
+/// plugs into an emulator waiting in `host`
+pub fn join(address: &str) -> Result<Box<dyn Cable>, String> {
+    #[cfg(unix)]
+    if let Some(path) = address.strip_prefix("unix:") {
+        use std::os::unix::net::UnixStream;
+        let stream = UnixStream::connect(path).map_err(|e| format!("could not connect to {path}: {e}"))?;
+        return Ok(Box::new(StreamCable::new(stream)?));
+    }
+    let stream = TcpStream::connect(address).map_err(|e| format!("could not connect to {address}: {e}"))?;
+    tcp_cable(stream)
+}
+
+fn tcp_cable(stream: TcpStream) -> Result<Box<dyn Cable>, String> {
+    // every sync is a tiny message, waiting to batch them up would stall both ends
+    stream.set_nodelay(true).map_err(|e| format!("link cable: {e}"))?;
+    Ok(Box::new(StreamCable::new(stream)?))
+}
+
+/// runs two gameboys plugged into each other until both have finished a
+/// frame. whichever is behind is always the one stepped, neither can stop
+/// and wait for the other or their syncs would stop lining up
+pub fn run_linked_frame(a: &mut GameBoy, b: &mut GameBoy) -> (Framebuffer, Framebuffer) {
+    let (mut a_frame, mut b_frame) = (None, None);
+    while a_frame.is_none() || b_frame.is_none() {
+        if a.cycles() <= b.cycles() {
+            if a.step() {
+                a_frame = Some(a.take_frame());
+            }
+        } else if b.step() {
+            b_frame = Some(b.take_frame());
+        }
+    }
+    (a_frame.unwrap(), b_frame.unwrap())
+}
+
+/// a cable between two gameboys in the same process
+pub fn connect(a: &mut GameBoy, b: &mut GameBoy) {
+    let (a_end, b_end) = local_pair();
+    a.plug_in(Box::new(a_end));
+    b.plug_in(Box::new(b_end));
+}
@@ -1,18 +1,16 @@
 #![allow(unused)]
-#![allow(unreachable_code)]
-#![allow(unsafe_code)]
 
-mod cpu;
-mod memory;
-mod registers;
-mod ppu;
-mod opcodes;
+use gameboy_emulator::{pallete_to_rgba, headless, debugger, gdbstub, viewers, link};
+use gameboy_emulator::gameboy::GameBoy;
+use gameboy_emulator::args::Args;
+use gameboy_emulator::rewind::Rewind;
+use gameboy_emulator::joypad::{Button, set_button};
+use gameboy_emulator::movie::Movie;
+use gameboy_emulator::pacing::FramePacer;
+use gameboy_emulator::framebuffer::Framebuffer;
+use gameboy_emulator::capture::Recorder;
 
-use cpu::Cpu;
-use ppu::Ppu;
-use memory::Memory;
-
-use std::{env, cell::RefCell, rc::Rc, time::Instant, fs::File, io::Write};
+use std::{time::Instant, fs::File, io::Write};
 use pixels::{SurfaceTexture, Pixels, Error};
 use winit::{
     dpi::LogicalSize, 
@@ -22,34 +20,47 @@ use winit::{
 };
 use winit_input_helper::WinitInputHelper;
 
-/// little endian reading;
-/// 
-/// the first number parsed will be the lower byte and the 
-/// second will be the upper byte.
-pub fn combine_u8s(lsb: u8, msb: u8) -> u16 {
-    ((msb as u16) << 8) + lsb as u16
-}
-/// the upper byte is returned first. the lower byte is
-/// returned secondly.
-pub fn split_u16(a: u16) -> (u8, u8) {
-    ((a >> 8) as u8, (a & 0xFF) as u8)
-}
-
-// this number represents the number of cycles which each scanline will use up
-const MAXCYCLES: usize = 453;
+fn main() {
+    let args = Args::parse();
+    if args.headless {
+        headless::run(&args);
+        return;
+    }
+    if args.debug {
+        debugger::run(&args);
+        return;
+    }
+    if let Some(port) = args.gdb {
+        gdbstub::run(&args, port);
+        return;
+    }
 
-const SCREEN_HEIGHT: u32 = 160;
-const SCREEN_WIDTH: u32 = 144;
+    let rom = args.rom();
+    let mut gameboy = GameBoy::new(rom.clone());
+    gameboy.tracer = args.tracer();
+    gameboy.profiler = args.profiler();
+    gameboy.memory.borrow_mut().cheats = args.cheats();
+    gameboy.memory.borrow_mut().lax_access = args.lax_access;
+    gameboy.memory.borrow_mut().doctor_ly = args.trace_doctor;
+    // sgb games are shown with their border
+    let (width, height) = gameboy.screen_size();
+    // the other gameboy on the link cable gets a window of its own
+    let mut partner = args.link_gameboy();
+    match &mut partner {
+        Some(other) => link::connect(&mut gameboy, other),
+        None => if let Some(cable) = args.cable() {
+            gameboy.plug_in(cable);
+        },
+    }
 
-fn main() {
     // setting up the window
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
     let mut input = WinitInputHelper::new();
 
     let window = {
-        let size = LogicalSize::new(SCREEN_WIDTH as f64, SCREEN_HEIGHT as f64);
-        let scaled_size = LogicalSize::new(SCREEN_WIDTH as f64 * 3.0, SCREEN_HEIGHT as f64 * 3.0);
+        let size = LogicalSize::new(width as f64, height as f64);
+        let scaled_size = LogicalSize::new(width as f64 * 3.0, height as f64 * 3.0);
         WindowBuilder::new()
             .with_title("gameboy emulator")
             .with_inner_size(scaled_size)
@@ -60,32 +71,74 @@ fn main() {
     let mut pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(SCREEN_WIDTH, SCREEN_HEIGHT, surface_texture).unwrap()
+        Pixels::new(width, height, surface_texture).unwrap()
     };
+    let mut partner_window = partner.as_ref().map(|other| {
+        let (width, height) = other.screen_size();
+        let window = WindowBuilder::new()
+            .with_title("gameboy emulator (link)")
+            .with_inner_size(LogicalSize::new(width as f64 * 3.0, height as f64 * 3.0))
+            .with_min_inner_size(LogicalSize::new(width as f64, height as f64))
+            .build(&event_loop)
+            .unwrap();
+        let window_size = window.inner_size();
+        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+        let pixels = Pixels::new(width, height, surface_texture).unwrap();
+        (window, pixels)
+    });
+    let mut partner_buttons = 0;
 
-    let rom = get_rom();
-
-    // all the pillars of a gameboy emulator
-    let memory = Rc::new(RefCell::new(Memory::new(rom)));
-    let mut cpu = Cpu::new(memory.clone());
-    let mut ppu = Ppu::new(memory.clone());
+    let mut buttons = 0;
+    // the movie is written out once the window closes
+    let mut recording = args.record.as_ref().map(|_| Movie::new(&rom, gameboy.save_state(), args.lax_access));
+    // which save state slot the F5/F8 keys use, changed with the number keys
+    let mut slot = 1;
+    // holding backspace steps back through the rewind buffer
+    let mut rewind = Rewind::new(args.rewind_mb * 1024 * 1024, args.rewind_interval);
+    let mut rewinding = false;
+    let mut pacer = FramePacer::new(args.ff_speed);
+    // F9 saves the last frame, F10 starts and stops recording it. --video records from the start
+    let mut last_frame = Framebuffer::default();
+    let mut video = args.recorder();
+    let mut dumper = args.dumper();
 
     // this cycle needs to run at below 16-milliseconds
     event_loop.run(move |event, elwt| {
-        let mut new_frame_data: Vec<u8> = Vec::new();
         // handling the screen/inputs
         // rendering isnt done here as it wouldnt be able to follow the timings i would want it to
         match event {
-            Event::WindowEvent { event, .. } => {
+            Event::WindowEvent { event, window_id } => {
+                // the linked gameboy's window only takes its own buttons
+                let partner_focused = partner_window.as_ref().is_some_and(|(w, _)| w.id() == window_id);
                 match event {
                     WindowEvent::CloseRequested => {
                         let mut debug_file = File::create("debug.gb").unwrap();
-                        debug_file.write_all(&memory.borrow().memory).unwrap();
+                        debug_file.write_all(&gameboy.memory.borrow().memory).unwrap();
                         elwt.exit();
                     }
                     WindowEvent::KeyboardInput { event, .. } => {
                         use winit::keyboard::{PhysicalKey::Code, KeyCode};
 
+                        // keys which are held down
+                        if event.physical_key == Code(KeyCode::Backspace) {
+                            rewinding = event.state.is_pressed();
+                        }
+                        if event.physical_key == Code(KeyCode::Tab) {
+                            pacer.fast_forward = event.state.is_pressed();
+                        }
+                        if let Code(e) = event.physical_key {
+                            if let Some(button) = key_to_button(e) {
+                                let held = if partner_focused { &mut partner_buttons } else { &mut buttons };
+                                set_button(held, button, event.state.is_pressed());
+                            }
+                        }
+                        if partner_focused {
+                            return;
+                        }
+                        // hotkeys only fire once per press
+                        if !event.state.is_pressed() || event.repeat {
+                            return;
+                        }
                         if let Code(e) = event.physical_key {
                             match e {
                                 // this space will be used to handle all the inputs
@@ -94,6 +147,58 @@ fn main() {
 
                                 // just as an experiment, q => quit
                                 KeyCode::KeyQ => elwt.exit(),
+
+                                KeyCode::KeyP => pacer.toggle_pause(),
+                                KeyCode::KeyN => pacer.advance_frame(),
+
+                                // save states
+                                KeyCode::Digit1 => slot = 1,
+                                KeyCode::Digit2 => slot = 2,
+                                KeyCode::Digit3 => slot = 3,
+                                KeyCode::Digit4 => slot = 4,
+                                KeyCode::Digit5 => slot = 5,
+                                KeyCode::F5 => save_state(&gameboy, &args.rom_path, slot),
+                                // loading would desync the movie from its inputs
+                                KeyCode::F8 if recording.is_some() => println!("cannot load states while recording"),
+                                KeyCode::F8 => load_state(&mut gameboy, &args.rom_path, slot),
+                                KeyCode::F12 => dump_vram(&gameboy, &args),
+                                KeyCode::F9 => {
+                                    let path = unused_path(&args.rom_path, "shot", "png");
+                                    match last_frame.save_png(&path, args.scale) {
+                                        Err(e) => println!("{e}"),
+                                        Ok(_) => println!("screenshot written to {path}"),
+                                    }
+                                }
+                                KeyCode::F10 => match video.take() {
+                                    Some(recorder) => finish_video(recorder),
+                                    None => {
+                                        let path = unused_path(&args.rom_path, "rec", "gif");
+                                        match Recorder::new(&path, args.scale) {
+                                            Err(e) => println!("{e}"),
+                                            Ok(r) => {
+                                                println!("recording to {path}");
+                                                video = Some(r);
+                                            }
+                                        }
+                                    }
+                                },
+                                // replays always start with the cheats from the file turned on
+                                KeyCode::F6 | KeyCode::F7 if recording.is_some() => println!("cannot change cheats while recording"),
+                                KeyCode::F6 => {
+                                    let cheats = &mut gameboy.memory.borrow_mut().cheats;
+                                    cheats.disabled = !cheats.disabled;
+                                    println!("cheats {}", if cheats.disabled { "off" } else { "on" });
+                                }
+                                // picks up any changes made to the cheat file, a broken one leaves the cheats as they were
+                                KeyCode::F7 => match args.load_cheats() {
+                                    Err(e) => println!("{e}"),
+                                    Ok(mut cheats) => {
+                                        let mut memory = gameboy.memory.borrow_mut();
+                                        cheats.disabled = memory.cheats.disabled;
+                                        println!("{} cheats loaded", cheats.cheats.len());
+                                        memory.cheats = cheats;
+                                    }
+                                },
                                 _ => ()
                             }
                         }
@@ -102,59 +207,154 @@ fn main() {
                 }
             },
             Event::AboutToWait => {
-                // run a scanlines worth of processing per scanline
-                for ly in 0..154 {
-                    // update the ly value for the ppu
-                    memory.borrow_mut().unchecked_write(0xFF44, ly);
-
-                    let mut cycles = 0;
-                    // handling the actual gameboy
-                    // this handles everything for one scan-line.
-                    while cycles < MAXCYCLES {
-                        let new_cycles = cpu.process_next();
-                        memory.borrow_mut().tick(new_cycles);
-                        cycles += new_cycles as usize;
+                // frames are only ran once they are due, the loop sleeps until then
+                let frame_due = pacer.frame_due(Instant::now());
+                match pacer.wake_at() {
+                    None => elwt.set_control_flow(ControlFlow::Poll),
+                    Some(at) => elwt.set_control_flow(ControlFlow::WaitUntil(at)),
+                }
+                if !frame_due {
+                    return;
+                }
+
+                // the same goes for rewinding, which would also leave the linked gameboy behind
+                let mut rewinding = rewinding && recording.is_none() && partner.is_none();
+                if rewinding {
+                    if let Some(state) = rewind.step_back() {
+                        // the older snapshots are built on this one, so they are no good either
+                        if let Err(e) = gameboy.load_state(&state) {
+                            println!("could not rewind: {e}");
+                            rewind.clear();
+                            rewinding = false;
+                        }
+                    }
+                }
+                gameboy.set_buttons(buttons);
+                let new_frame_data = match (&mut partner, &mut partner_window) {
+                    (Some(other), Some((window, pixels))) => {
+                        other.set_buttons(partner_buttons);
+                        let (frame, other_frame) = link::run_linked_frame(&mut gameboy, other);
+                        draw_frame(pixels, &other_frame);
+                        pixels.render().unwrap();
+                        window.request_redraw();
+                        frame
                     }
-                    // get the new scanline ready
-                    new_frame_data.append(&mut ppu.draw_scanline());
+                    _ => gameboy.run_frame(),
+                };
+                if !rewinding {
+                    rewind.frame_finished(&gameboy);
                 }
-                for (i, pixel) in pixels.frame_mut().chunks_exact_mut(4).enumerate() {
-                    let new_pixel = pallete_to_rgba(new_frame_data[i]);
-                    pixel[0] = new_pixel.0;
-                    pixel[1] = new_pixel.1;
-                    pixel[2] = new_pixel.2;
-                    pixel[3] = 255;
+                if let Some(movie) = &mut recording {
+                    movie.inputs.push(buttons);
                 }
+                draw_frame(&mut pixels, &new_frame_data);
+                if let Some(recorder) = &mut video {
+                    if let Err(e) = recorder.frame(&new_frame_data) {
+                        println!("{e}");
+                        video = None;
+                    }
+                }
+                if let Some(d) = &mut dumper {
+                    // there is no sound hardware to take samples from yet
+                    if let Err(e) = d.frame(&new_frame_data, &[]) {
+                        println!("{e}");
+                        dumper = None;
+                    }
+                }
+                last_frame = new_frame_data;
 
                 // render the frame
                 pixels.render().unwrap();
                 window.request_redraw();
             },
+            Event::LoopExiting => {
+                if let (Some(movie), Some(path)) = (&recording, &args.record) {
+                    match std::fs::write(path, movie.to_bytes()) {
+                        Err(e) => println!("could not write {path}: {e}"),
+                        Ok(_) => println!("recorded {} frames to {path}", movie.inputs.len()),
+                    }
+                }
+                if let Some(recorder) = video.take() {
+                    finish_video(recorder);
+                }
+                if let Some(d) = dumper.take() {
+                    match d.finish() {
+                        Err(e) => println!("{e}"),
+                        Ok(n) => println!("dumped {n} frames"),
+                    }
+                }
+                // dropping it writes the profile out, and the printer's last page
+                gameboy.profiler.take();
+                gameboy.unplug();
+            }
             _ => ()
         }
     });
 }
 
-fn get_rom() -> Vec<u8> {
-    let args: Vec<String> = env::args().collect();
+fn draw_frame(pixels: &mut Pixels, frame: &Framebuffer) {
+    pixels.frame_mut().copy_from_slice(&frame.to_rgba());
+}
 
-    // no file path provided
-    if args.len() == 1 {
-        panic!("no file path was provided");
+fn key_to_button(key: winit::keyboard::KeyCode) -> Option<Button> {
+    use winit::keyboard::KeyCode;
+    match key {
+        KeyCode::ArrowRight => Some(Button::Right),
+        KeyCode::ArrowLeft => Some(Button::Left),
+        KeyCode::ArrowUp => Some(Button::Up),
+        KeyCode::ArrowDown => Some(Button::Down),
+        KeyCode::KeyX => Some(Button::A),
+        KeyCode::KeyZ => Some(Button::B),
+        KeyCode::ShiftRight => Some(Button::Select),
+        KeyCode::Enter => Some(Button::Start),
+        _ => None,
     }
-    let rom_path = &args[1];
-    match std::fs::read(rom_path) {
-        Err(_) => panic!("invalid file provided"),
-        Ok(f) => f,
+}
+
+/// save states live next to the rom as `<rom>.ss<slot>`
+fn save_state(gameboy: &GameBoy, rom_path: &str, slot: u8) {
+    let path = format!("{rom_path}.ss{slot}");
+    match std::fs::write(&path, gameboy.save_state()) {
+        Err(e) => println!("could not write {path}: {e}"),
+        Ok(_) => println!("saved state to slot {slot}"),
+    }
+}
+fn load_state(gameboy: &mut GameBoy, rom_path: &str, slot: u8) {
+    let path = format!("{rom_path}.ss{slot}");
+    let data = match std::fs::read(&path) {
+        Err(_) => return println!("slot {slot} is empty"),
+        Ok(d) => d,
+    };
+    match gameboy.load_state(&data) {
+        Err(e) => println!("could not load slot {slot}: {e}"),
+        Ok(_) => println!("loaded state from slot {slot}"),
     }
 }
 
-fn pallete_to_rgba(i: u8) -> (u8, u8, u8) {
-    match i {
-        0 => (0x00, 0x00, 0x00),
-        1 => (0x50, 0x50, 0x50),
-        2 => (0xA0, 0xA0, 0xA0),
-        3 => (0xFF, 0xFF, 0xFF),
-        _ => panic!("invalid pallete index"),
+/// screenshots and recordings go next to the rom as `<rom>.<kind><n>.<extension>`,
+/// numbered from 1 so earlier ones arent overwritten
+fn unused_path(rom_path: &str, kind: &str, extension: &str) -> String {
+    (1..).map(|n| format!("{rom_path}.{kind}{n}.{extension}"))
+        .find(|path| !std::path::Path::new(path).exists())
+        .unwrap()
+}
+
+fn finish_video(recorder: Recorder) {
+    let path = recorder.path().to_string();
+    match recorder.finish() {
+        Err(e) => println!("{e}"),
+        Ok(n) => println!("recorded {n} frames to {path}"),
+    }
+}
+
+/// the vram viewers go in `<rom>.vram/`, or wherever --dump-vram says
+fn dump_vram(gameboy: &GameBoy, args: &Args) {
+    let dir = match &args.dump_vram {
+        Some(d) => d.clone(),
+        None => format!("{}.vram", args.rom_path),
+    };
+    match viewers::dump(&gameboy.memory.borrow(), &dir, args.tile_palette) {
+        Err(e) => println!("{e}"),
+        Ok(_) => println!("vram written to {dir}"),
     }
-}
\ No newline at end of file
+}
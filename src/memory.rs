@@ -1,6 +1,14 @@
-use std::fs;
+use std::{fs, cell::Cell};
 
 use crate::combine_u8s;
+use crate::joypad::read_p1;
+use crate::debugger::{Access, Watchpoint, WatchHit};
+use crate::savestate::{SaveState, StateWriter, StateReader};
+use crate::cheats::Cheats;
+use crate::cgb::{self, Cgb};
+use crate::io;
+use crate::sgb::{self, Sgb};
+use crate::serial::{self, Serial, Message};
 
 /// just makes it more clear what my magic numbers are
 /// also since the timings are handled by the memory instead of the CPU
@@ -12,16 +20,76 @@ enum TimingRegisters {
     TAC=0xFF07,
 }
 
+const POST_BOOT_IO: [(usize, u8); 39] = [
+    (0xFF00, 0xCF), (0xFF01, 0x00), (0xFF02, 0x7E), (0xFF04, 0xAB), (0xFF05, 0x00),
+    (0xFF06, 0x00), (0xFF07, 0xF8), (0xFF0F, 0xE1), (0xFF10, 0x80), (0xFF11, 0xBF),
+    (0xFF12, 0xF3), (0xFF13, 0xFF), (0xFF14, 0xBF), (0xFF16, 0x3F), (0xFF17, 0x00),
+    (0xFF18, 0xFF), (0xFF19, 0xBF), (0xFF1A, 0x7F), (0xFF1B, 0xFF), (0xFF1C, 0x9F),
+    (0xFF1D, 0xFF), (0xFF1E, 0xBF), (0xFF20, 0xFF), (0xFF21, 0x00), (0xFF22, 0x00),
+    (0xFF23, 0xBF), (0xFF24, 0x77), (0xFF25, 0xF3), (0xFF26, 0xF1), (0xFF40, 0x91),
+    (0xFF41, 0x85), (0xFF42, 0x00), (0xFF43, 0x00), (0xFF44, 0x00), (0xFF45, 0x00),
+    (0xFF46, 0xFF), (0xFF47, 0xFC), (0xFF4A, 0x00), (0xFF4B, 0x00),
+];
+
 pub struct Memory {
     pub memory: Vec<u8>,
     div: u16,
     overflow: bool,
+    // the held buttons, see `joypad::Button` for the layout
+    buttons: u8,
+    // set by the debugger, any cpu access inside of these is remembered
+    pub watchpoints: Vec<Watchpoint>,
+    watch_hit: Cell<Option<WatchHit>>,
+    // addresses held at a value, writes to them are ignored
+    pub frozen: Vec<(u16, u8)>,
+    pub cheats: Cheats,
+    // only there when the cartridge header asks for a game boy color
+    pub cgb: Option<Cgb>,
+    // only there for dmg games which say they support the super game boy
+    pub sgb: Option<Sgb>,
+    pub serial: Serial,
+    // the mode the ppu is in, the cpu cant get at vram while it is drawing
+    // or at oam while it is being searched
+    pub ppu_mode: u8,
+    // lets the cpu at them whatever the mode, for roms which only work on lax emulators
+    pub lax_access: bool,
+    // the cpu always reads LY as 0x90 (the first line of vblank), which is how
+    // gameboy doctor's logs were made so traces dont drift from them on LY loops
+    pub doctor_ly: bool,
+    // the oam dma being copied, and the source of one written to FF46 which starts next cycle
+    oam_dma: Option<OamDma>,
+    oam_dma_starting: Option<u16>,
+    // where oam dma is copying from this cycle, and the byte it copied
+    dma_reading: Option<u16>,
+    dma_byte: u8,
+    // how many cycles of this instruction the cpu's accesses have already moved oam dma on
+    dma_stepped: u8,
+}
+
+/// oam dma copies a byte a cycle into oam, taking 160 cycles
+#[derive(Clone, Copy)]
+struct OamDma {
+    source: u16,
+    copied: u8,
+}
+
+/// while oam dma is copying the cpu can only get at the io registers and
+/// hram, anything on the same bus as the dma gets whatever it is reading
+#[derive(PartialEq)]
+enum Bus {
+    External,
+    Vram,
+    // the cgb's work ram has a bus of its own
+    Wram,
+    Internal,
 }
 
 impl Memory {
     pub fn new(rom: Vec<u8>) -> Self {
         // for now all the roms will only be up to 0x8000 in length
         // so we can just extend till we reach the quota
+        let cgb = if cgb::is_cgb_rom(&rom) { Some(Cgb::default()) } else { None };
+        let sgb = if cgb.is_none() && sgb::is_sgb_rom(&rom) { Some(Sgb::default()) } else { None };
         let mut memory = rom;
         if memory.len() > 0x8000 {
             panic!("not going to handle these yet")
@@ -29,15 +97,78 @@ impl Memory {
         let padding_amount = 65536 - memory.len();
         let padding_vec = vec![0; padding_amount];
         memory.extend(padding_vec);
-        Self { memory, div: 0, overflow: false }
+        // the io registers as the boot rom leaves them
+        for (address, value) in POST_BOOT_IO {
+            memory[address] = value;
+        }
+        Self {
+            memory,
+            div: 0xAB00,
+            overflow: false,
+            buttons: 0,
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
+            frozen: Vec::new(),
+            cheats: Cheats::default(),
+            cgb,
+            sgb,
+            serial: Serial::default(),
+            ppu_mode: 0,
+            lax_access: false,
+            doctor_ly: false,
+            oam_dma: None,
+            oam_dma_starting: None,
+            dma_reading: None,
+            dma_byte: 0xFF,
+            dma_stepped: 0,
+        }
     }
 
-    // this will just be oam dma
-    pub fn dma(&mut self, pos: u8) {
-        let src: usize = ((pos as u16) << 8) as usize;
-        let dst: usize = 0xFE00;
-        for i in 0..160 {
-            self.memory[dst+i] = self.memory[src+i];
+    /// moves oam dma on by one cycle. a dma written while another is going
+    /// leaves the old one copying for the cycle before the new one starts
+    fn oam_dma_cycle(&mut self) {
+        self.dma_reading = None;
+        if let Some(mut dma) = self.oam_dma.take() {
+            // the top of memory cant be copied from, it gets echo ram instead
+            let source = dma.source + dma.copied as u16;
+            let source = if source >= 0xE000 { source - 0x2000 } else { source };
+            self.dma_byte = self.peek(source);
+            self.memory[0xFE00 + dma.copied as usize] = self.dma_byte;
+            self.dma_reading = Some(source);
+            dma.copied += 1;
+            if dma.copied < 160 {
+                self.oam_dma = Some(dma);
+            }
+        }
+        if let Some(source) = self.oam_dma_starting.take() {
+            self.oam_dma = Some(OamDma { source, copied: 0 });
+        }
+    }
+
+    /// each cpu access is a cycle, oam dma is kept in step with them
+    pub fn cpu_cycle(&mut self) {
+        self.oam_dma_cycle();
+        self.dma_stepped += 1;
+    }
+
+    fn bus(&self, address: u16) -> Bus {
+        match address {
+            0x8000..=0x9FFF => Bus::Vram,
+            0xC000..=0xFDFF if self.is_cgb() => Bus::Wram,
+            0xFE00..=0xFFFF => Bus::Internal,
+            _ => Bus::External,
+        }
+    }
+
+    /// whether the cpu cant get at the address because of oam dma
+    fn dma_blocks(&self, address: u16) -> bool {
+        let Some(source) = self.dma_reading else {
+            return false;
+        };
+        match address {
+            0xFE00..=0xFEFF => true,
+            0xFF00..=0xFFFF => false,
+            _ => self.bus(address) == self.bus(source),
         }
     }
 
@@ -68,36 +199,309 @@ impl Memory {
 
         // only map the top 8 bits to the memory
         self.memory[TimingRegisters::DIV as usize] = (self.div >> 8) as u8;
+
+        if let Some(byte) = self.serial.tick(cycles as u32) {
+            self.serial_finished(byte);
+        }
+
+        // the cycles the cpu didnt spend on memory still move oam dma on
+        for _ in self.dma_stepped..cycles / 4 {
+            self.oam_dma_cycle();
+        }
+        self.dma_stepped = 0;
     }
 
     /// used as the internal way to writing to read only addresses
     /// the gameboy ROM never uses this only the emulator does
     pub fn unchecked_write(&mut self, address: u16, data: u8) {
+        let address = io::unecho(address);
+        if let Some(cgb) = &mut self.cgb {
+            if cgb.write(&mut self.memory, address, data) {
+                return;
+            }
+        }
         self.memory[address as usize] = data;
     }
 
     pub fn write(&mut self, address: u16, data: u8) {
-        let address = address as usize;
-        if address < 0x8000 {
-            //panic!("cannot handle swapping yet, {address}");
+        self.check_watchpoints(address, Access::Write, data);
+        if self.dma_blocks(address) || self.ppu_blocks(address) {
+            return;
+        }
+        let data = match self.frozen.iter().find(|(a, _)| *a == address) {
+            Some((_, value)) => *value,
+            None => data,
+        };
+        let address = io::unecho(address);
+        match address {
+            0x0000..=0x7FFF => {
+                //panic!("cannot handle swapping yet, {address}");
+            }
+            // nothing is there to write to
+            0xFEA0..=0xFEFF => return,
+            0xFF00..=0xFF7F => return self.write_io(address, data),
+            _ => {}
         }
+        if let Some(cgb) = &mut self.cgb {
+            if cgb.write(&mut self.memory, address, data) {
+                return;
+            }
+        }
+        self.memory[address as usize] = data;
+    }
+
+    /// only the register's writable bits change, then whatever writing it sets off
+    fn write_io(&mut self, address: u16, data: u8) {
+        if let Some(cgb) = &mut self.cgb {
+            if cgb.write(&mut self.memory, address, data) {
+                return;
+            }
+        }
+        let writable = io::register(address, self.is_cgb()).writable;
+        let old = self.memory[address as usize];
+        self.memory[address as usize] = (old & !writable) | (data & writable);
+        match address {
+            io::DIV => {
+                self.div = 0;
+                self.memory[io::DIV as usize] = 0;
+            }
+            // the sgb's commands are sent through the selection bits
+            io::P1 => {
+                if let Some(sgb) = &mut self.sgb {
+                    sgb.write_p1(data);
+                }
+            }
+            serial::SC => {
+                let (sb, sc) = (self.memory[serial::SB as usize], self.memory[serial::SC as usize]);
+                self.serial.start(sb, sc, self.is_cgb());
+            }
+            // oam dma starts a cycle after this one
+            io::DMA => self.oam_dma_starting = Some((data as u16) << 8),
+            cgb::HDMA5 if self.is_cgb() => self.start_hdma(data),
+            _ => {}
+        }
+    }
+    pub fn read(&self, address: u16) -> u8 {
+        let data = match self.dma_blocks(address) {
+            // oam reads as 0xFF, the dma's bus gives the byte being copied
+            true if (0xFE00..=0xFEFF).contains(&address) => 0xFF,
+            true => self.dma_byte,
+            false if self.ppu_blocks(address) => 0xFF,
+            false if self.doctor_ly && address == io::LY => 0x90,
+            false => self.peek(address),
+        };
+        self.check_watchpoints(address, Access::Read, data);
+        data
+    }
+    /// reads without setting off any watchpoints, for the ppu and debugger
+    pub fn peek(&self, address: u16) -> u8 {
+        let address = io::unecho(address);
+        match address {
+            0x0000..=0x7FFF => return self.cheats.patch_rom(address, self.memory[address as usize]),
+            0xFEA0..=0xFEFF => return io::unusable(address, self.is_cgb()),
+            io::P1 => {
+                let select = self.memory[io::P1 as usize];
+                let buttons = read_p1(select, self.buttons);
+                return match &self.sgb {
+                    Some(sgb) => sgb.read_p1(select, buttons),
+                    None => buttons,
+                };
+            }
+            _ => {}
+        }
+        if let Some(value) = self.cgb.as_ref().and_then(|cgb| cgb.read(&self.memory, address)) {
+            return value;
+        }
+        let value = self.memory[address as usize];
+        match address {
+            0xFF00..=0xFF7F => value | io::register(address, self.is_cgb()).unused,
+            _ => value,
+        }
+    }
+
+    /// whether the cpu cant get at the address because of the ppu's mode,
+    /// vram is being drawn from in mode 3 and oam is used in modes 2 and 3
+    fn ppu_blocks(&self, address: u16) -> bool {
+        if self.lax_access {
+            return false;
+        }
+        match address {
+            0x8000..=0x9FFF => self.ppu_mode == 3,
+            0xFE00..=0xFEFF => self.ppu_mode >= 2,
+            _ => false,
+        }
+    }
+
+    fn check_watchpoints(&self, address: u16, access: Access, value: u8) {
+        if self.watchpoints.iter().any(|w| w.matches(address, access)) {
+            self.watch_hit.set(Some(WatchHit { address, access, value }));
+        }
+    }
+    pub fn is_cgb(&self) -> bool {
+        self.cgb.is_some()
+    }
+    pub fn double_speed(&self) -> bool {
+        self.is_cgb() && self.memory[cgb::KEY1 as usize] & 0x80 != 0
+    }
+    /// STOP changes speed instead of stopping when KEY1 has the switch armed,
+    /// returns whether it did
+    pub fn switch_speed(&mut self) -> bool {
+        let key1 = self.memory[cgb::KEY1 as usize];
+        if !self.is_cgb() || key1 & 1 == 0 {
+            return false;
+        }
+        self.memory[cgb::KEY1 as usize] = (key1 ^ 0x80) & 0x80;
+        true
+    }
 
-        // this address means dma is starting
-        if address == 0xFF46 {
-            self.dma(data)
-        } else if address == 0xFF44 {
+    /// writing to HDMA5 starts a vram dma, or stops an hblank one with bit 7 clear
+    fn start_hdma(&mut self, data: u8) {
+        let io = |a: u16| self.memory[a as usize] as u16;
+        let source = ((io(cgb::HDMA1) << 8) | io(cgb::HDMA2)) & 0xFFF0;
+        let dest = 0x8000 | (((io(cgb::HDMA3) << 8) | io(cgb::HDMA4)) & 0x1FF0);
+        let Some(cgb) = &mut self.cgb else { return };
+        let hdma = &mut cgb.hdma;
+        if hdma.blocks != 0 && data & 0x80 == 0 {
+            hdma.status = 0x80 | (hdma.blocks - 1);
+            hdma.blocks = 0;
             return;
         }
+        hdma.source = source;
+        hdma.dest = dest;
+        let blocks = (data & 0x7F) + 1;
+        if data & 0x80 != 0 {
+            hdma.blocks = blocks;
+            return;
+        }
+        // general purpose, all at once
+        for _ in 0..blocks {
+            self.hdma_block();
+        }
+        if let Some(cgb) = &mut self.cgb {
+            cgb.hdma.status = 0xFF;
+        }
+    }
 
-        self.memory[address] = data;
-        if address >= 0xC000 && address <= 0xDE00 {
-            self.memory[address+0x2000] = data;
-        } else if address >= 0xE000 && address <= 0xFE00 {
-            self.memory[address-0x2000] = data;
+    /// copies 16 bytes into vram and halts the cpu for as long as that takes,
+    /// which is the same time at either speed so twice the cycles at double speed
+    fn hdma_block(&mut self) {
+        let double_speed = self.double_speed();
+        let Some(cgb) = &mut self.cgb else { return };
+        let (source, dest) = (cgb.hdma.source, cgb.hdma.dest);
+        cgb.hdma.source = source.wrapping_add(16);
+        cgb.hdma.dest = 0x8000 | (dest.wrapping_add(16) & 0x1FF0);
+        cgb.hdma.stall += if double_speed { 64 } else { 32 };
+        for i in 0..16 {
+            let value = self.peek(source.wrapping_add(i));
+            // vram is written through VBK's bank, and stops at the end of vram
+            let address = dest + i;
+            if address <= 0x9FFF {
+                self.unchecked_write(address, value);
+            }
         }
     }
-    pub fn read(&self, address: u16) -> u8 {
-        self.memory[address as usize]
+
+    /// called as each visible scanline enters mode 0, for the hblank dma
+    pub fn hblank_started(&mut self) {
+        let Some(cgb) = &self.cgb else { return };
+        if cgb.hdma.blocks == 0 {
+            return;
+        }
+        self.hdma_block();
+        if let Some(cgb) = &mut self.cgb {
+            cgb.hdma.blocks -= 1;
+            if cgb.hdma.blocks == 0 {
+                cgb.hdma.status = 0xFF;
+            }
+        }
+    }
+
+    pub fn dma_stalled(&self) -> bool {
+        self.cgb.as_ref().is_some_and(|cgb| cgb.hdma.stall > 0)
+    }
+    /// takes up to `max` cycles of the cpu being halted by a vram dma
+    pub fn take_dma_stall(&mut self, max: u8) -> u8 {
+        let Some(cgb) = &mut self.cgb else { return 0 };
+        let cycles = cgb.hdma.stall.min(max as u32);
+        cgb.hdma.stall -= cycles;
+        cycles as u8
+    }
+
+    /// keeps the address at the value until it is unfrozen
+    pub fn freeze(&mut self, address: u16, value: u8) {
+        self.unfreeze(address);
+        self.frozen.push((address, value));
+        self.unchecked_write(address, value);
+    }
+    pub fn unfreeze(&mut self, address: u16) {
+        self.frozen.retain(|(a, _)| *a != address);
+    }
+    /// the last watchpoint hit since this was called
+    pub fn take_watch_hit(&self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+    /// which rom bank an address currently maps to. there is no banking
+    /// yet so the upper half of the rom is always bank 1
+    pub fn rom_bank(&self, address: u16) -> u8 {
+        if address < 0x4000 { 0 } else { 1 }
+    }
+
+    /// updates which buttons are held. any newly pressed button
+    /// requests the joypad interrupt
+    pub fn set_buttons(&mut self, buttons: u8) {
+        if buttons & !self.buttons != 0 {
+            self.memory[0xFF0F] |= 0b0001_0000;
+        }
+        self.buttons = buttons;
+    }
+
+    /// SB gets what was shifted in, and the transfer flag is cleared with an interrupt
+    fn serial_finished(&mut self, byte: u8) {
+        self.memory[serial::SB as usize] = byte;
+        self.memory[serial::SC as usize] &= 0x7F;
+        self.memory[io::IF as usize] |= 0b0000_1000;
+    }
+
+    /// a message from the other end of the link cable
+    pub fn link_receive(&mut self, message: Message) {
+        let (sb, sc) = (self.memory[serial::SB as usize], self.memory[serial::SC as usize]);
+        if let Some(byte) = message.start.and_then(|b| self.serial.receive_start(b, sb, sc)) {
+            self.serial_finished(byte);
+        }
+        if let Some(byte) = message.reply.and_then(|b| self.serial.receive_reply(b)) {
+            self.serial_finished(byte);
+        }
+    }
+
+    /// the sgb takes CHR_TRN and PCT_TRN data from the frame after the
+    /// command, this is called as each frame finishes
+    pub fn sgb_frame(&mut self) {
+        if !self.sgb.as_ref().is_some_and(|sgb| sgb.transfer_pending()) {
+            return;
+        }
+        let data = self.screen_tile_data();
+        if let Some(sgb) = &mut self.sgb {
+            sgb.transfer(&data);
+        }
+    }
+
+    /// the 4KB of tiles making up the top of the screen, 20 to a row through
+    /// the background map. games line the tiles up in order with BGP left as
+    /// it is so the sgb can read its data straight out of the picture
+    fn screen_tile_data(&self) -> Vec<u8> {
+        let lcdc = self.memory[0xFF40];
+        let map = self.read_map((lcdc >> 3) & 1);
+        let mut data = Vec::with_capacity(4096);
+        for i in 0..256 {
+            let index = map[(i / 20) * 32 + i % 20];
+            let address = if lcdc & 0b0001_0000 != 0 {
+                0x8000 + index as usize * 16
+            } else {
+                (0x9000 + index as i8 as isize * 16) as usize
+            };
+            data.extend_from_slice(&self.memory[address..address + 16]);
+        }
+        data
     }
 
     /// these are all the functions for collecting pixel data for the ppu
@@ -112,12 +516,25 @@ impl Memory {
     /// is stored within the u16s rather than two u8s
     pub fn read_tile(&self, index: u8) -> [u16; 8] {
         // just going to assume its 8000 addressing
-        let src = 0x8000 + (index as usize)*16;
+        self.read_tile_at(0x8000 + (index as u16)*16)
+    }
+    /// the same as `read_tile` but for a tile anywhere in vram,
+    /// which is needed to reach the 128 tiles past 0x9000
+    pub fn read_tile_at(&self, address: u16) -> [u16; 8] {
+        self.read_tile_in_bank(address, 0)
+    }
+    /// the cgb has a second bank of tiles, which is just empty on the dmg
+    pub fn read_tile_in_bank(&self, address: u16, bank: u8) -> [u16; 8] {
+        let vram = match &self.cgb {
+            Some(cgb) if bank == 1 => &cgb.vram1[..],
+            _ => &self.memory[0x8000..0xA000],
+        };
+        let src = address as usize - 0x8000;
         let mut tile_data = vec![0; 8];
         for i in 0..8 {
             let mut row_data: u16 = 0;
-            let lsb = self.memory[src+i*2];
-            let msb = self.memory[src+i*2+1];
+            let lsb = vram[src+i*2];
+            let msb = vram[src+i*2+1];
             for j in 0..8 {
                 row_data = row_data << 2;
                 
@@ -139,4 +556,88 @@ impl Memory {
         let address = 0x9800 + ((index as usize)*1024);
         self.memory[address..(address+1024)].try_into().unwrap()
     }
-}
\ No newline at end of file
+    /// the cgb attributes for each tile of a map, which sit in vram bank 1 under it.
+    /// bits 0-2 are the palette, 3 the tile's bank, 5 and 6 x/y flip and 7 priority
+    pub fn read_attributes(&self, index: u8) -> [u8; 1024] {
+        let Some(cgb) = &self.cgb else { return [0; 1024] };
+        let address = 0x1800 + (index as usize)*1024;
+        cgb.vram1[address..(address+1024)].try_into().unwrap()
+    }
+}
+
+impl SaveState for Memory {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.bytes(&self.memory);
+        writer.u16(self.div);
+        writer.bool(self.overflow);
+        writer.bool(self.cgb.is_some());
+        if let Some(cgb) = &self.cgb {
+            cgb.save_state(writer);
+        }
+        writer.bool(self.sgb.is_some());
+        if let Some(sgb) = &self.sgb {
+            sgb.save_state(writer);
+        }
+        self.serial.save_state(writer);
+        writer.bool(self.oam_dma.is_some());
+        if let Some(dma) = self.oam_dma {
+            writer.u16(dma.source);
+            writer.u8(dma.copied);
+        }
+        writer.bool(self.oam_dma_starting.is_some());
+        writer.u16(self.oam_dma_starting.unwrap_or(0));
+        writer.bool(self.dma_reading.is_some());
+        writer.u16(self.dma_reading.unwrap_or(0));
+        writer.u8(self.dma_byte);
+    }
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let memory = reader.bytes()?;
+        if memory.len() != self.memory.len() {
+            return Err("save state memory is the wrong size".to_string());
+        }
+        let div = reader.u16()?;
+        let overflow = reader.bool()?;
+        // version 1 was from before the cgb
+        let cgb = reader.version >= 2 && reader.bool()?;
+        if cgb != self.cgb.is_some() {
+            return Err("save state is from a different game boy model".to_string());
+        }
+        self.memory.copy_from_slice(memory);
+        self.div = div;
+        self.overflow = overflow;
+        if let Some(state) = &mut self.cgb {
+            state.load_state(reader)?;
+        }
+        // version 4 was from before the sgb
+        let sgb = reader.version >= 5 && reader.bool()?;
+        if reader.version >= 5 && sgb != self.sgb.is_some() {
+            return Err("save state is from a different game boy model".to_string());
+        }
+        if let (true, Some(state)) = (sgb, &mut self.sgb) {
+            state.load_state(reader)?;
+        }
+        self.serial.load_state(reader)?;
+        // version 3 was from before oam dma took any time
+        self.oam_dma = None;
+        self.oam_dma_starting = None;
+        self.dma_reading = None;
+        if reader.version >= 4 {
+            if reader.bool()? {
+                let dma = OamDma { source: reader.u16()?, copied: reader.u8()? };
+                // a dma is over once it has copied the whole of oam
+                if dma.copied >= 160 {
+                    return Err("save state oam dma is past the end of oam".to_string());
+                }
+                self.oam_dma = Some(dma);
+            }
+            let starting = reader.bool()?;
+            let source = reader.u16()?;
+            self.oam_dma_starting = starting.then_some(source);
+            let reading = reader.bool()?;
+            let source = reader.u16()?;
+            self.dma_reading = reading.then_some(source);
+            self.dma_byte = reader.u8()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::png::crc32;
+
+/// a recording of the buttons held on every frame, along with the state
+/// the recording started from. replaying the same inputs from the same
+/// state gives exactly the same frames since the core never looks at the clock.
+///
+///  _____________________________________________________________________
+/// | "GBMV" | version: u16 | rom crc32: u32 | flags: u8 |               |
+/// | state (len: u32, bytes) | frames: u32 | buttons: one per frame... |
+///  ---------------------------------------------------------------------
+///
+/// the flags are the options which change how the game runs, so the
+/// replay runs the same way. bit 0 is `--lax-access`
+const MAGIC: &[u8; 4] = b"GBMV";
+const VERSION: u16 = 2;
+
+pub struct Movie {
+    pub rom_crc: u32,
+    // whether it was recorded with the ppu letting the cpu at vram and oam whatever the mode
+    pub lax_access: bool,
+    pub start_state: Vec<u8>,
+    pub inputs: Vec<u8>,
+}
+
+impl Movie {
+    pub fn new(rom: &[u8], start_state: Vec<u8>, lax_access: bool) -> Self {
+        Self {
+            rom_crc: crc32(rom),
+            lax_access,
+            start_state,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend(VERSION.to_le_bytes());
+        out.extend(self.rom_crc.to_le_bytes());
+        out.push(self.lax_access as u8);
+        out.extend((self.start_state.len() as u32).to_le_bytes());
+        out.extend(&self.start_state);
+        out.extend((self.inputs.len() as u32).to_le_bytes());
+        out.extend(&self.inputs);
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 6 || &data[0..4] != MAGIC {
+            return Err("not a movie file".to_string());
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version > VERSION {
+            return Err(format!("movie version {version} is newer than this emulator"));
+        }
+
+        let mut pos = 6;
+        let rom_crc = read_u32(data, &mut pos)?;
+        // version 1 was from before the flags, when the cpu was never blocked
+        let flags = match version {
+            1 => 1,
+            _ => take(data, &mut pos, 1)?[0],
+        };
+        let state_len = read_u32(data, &mut pos)? as usize;
+        let start_state = take(data, &mut pos, state_len)?.to_vec();
+        let frames = read_u32(data, &mut pos)? as usize;
+        let inputs = take(data, &mut pos, frames)?.to_vec();
+        Ok(Self { rom_crc, lax_access: flags & 1 != 0, start_state, inputs })
+    }
+
+    /// movies are only meant to be played back on the rom they were recorded on
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), String> {
+        let crc = crc32(rom);
+        if crc != self.rom_crc {
+            return Err(format!("movie was recorded on a rom with crc {:08x}, this one is {crc:08x}", self.rom_crc));
+        }
+        Ok(())
+    }
+}
+
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+    match data.get(*pos..*pos+len) {
+        None => Err("movie file ended early".to_string()),
+        Some(s) => {
+            *pos += len;
+            Ok(s)
+        }
+    }
+}
+fn read_u32(data: &[u8], pos: &mut usize) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(take(data, pos, 4)?.try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for lax_access in [false, true] {
+            let mut movie = Movie::new(b"rom", vec![1, 2, 3], lax_access);
+            movie.inputs = vec![0, 0x10, 0xFF];
+            let read = Movie::from_bytes(&movie.to_bytes()).unwrap();
+            assert_eq!(read.rom_crc, crc32(b"rom"));
+            assert_eq!(read.lax_access, lax_access);
+            assert_eq!(read.start_state, vec![1, 2, 3]);
+            assert_eq!(read.inputs, vec![0, 0x10, 0xFF]);
+        }
+    }
+
+    #[test]
+    fn version_1_was_never_blocked() {
+        let mut data = MAGIC.to_vec();
+        data.extend(1u16.to_le_bytes());
+        data.extend(crc32(b"rom").to_le_bytes());
+        data.extend(1u32.to_le_bytes());
+        data.push(7);
+        data.extend(2u32.to_le_bytes());
+        data.extend([0x01, 0x02]);
+        let movie = Movie::from_bytes(&data).unwrap();
+        assert!(movie.lax_access);
+        assert_eq!((movie.start_state, movie.inputs), (vec![7], vec![0x01, 0x02]));
+        assert!(Movie::from_bytes(&data[..data.len() - 1]).is_err());
+    }
+}
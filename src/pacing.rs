@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+
+use crate::gameboy::{MAXCYCLES, SCANLINES};
+
+/// a frame is 154 scanlines of 456 T-cycles, 70224 in all
+pub const CYCLES_PER_FRAME: u32 = (SCANLINES * MAXCYCLES) as u32;
+pub const CLOCK_SPEED: u32 = 4_194_304;
+
+// if we ever fall this many frames behind (the window was dragged, the
+// machine is too slow...) the frames are dropped rather than rushed through
+const MAX_FRAMES_BEHIND: u32 = 4;
+
+/// keeps the frontend running frames at the real ~59.73 Hz.
+///
+/// the core itself never looks at the time, this only decides when
+/// the next whole frame should be ran.
+pub struct FramePacer {
+    next_frame: Instant,
+    frame_time: Duration,
+    // how many times faster frames are ran while fast forwarding, 0 is uncapped
+    fast_forward_speed: u32,
+    pub fast_forward: bool,
+    pub paused: bool,
+    // a single frame to run while paused
+    advance: bool,
+}
+
+impl FramePacer {
+    pub fn new(fast_forward_speed: u32) -> Self {
+        let frame_time = Duration::from_secs_f64(CYCLES_PER_FRAME as f64 / CLOCK_SPEED as f64);
+        Self {
+            next_frame: Instant::now(),
+            frame_time,
+            fast_forward_speed,
+            fast_forward: false,
+            paused: false,
+            advance: false,
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.next_frame = Instant::now();
+    }
+    /// runs one frame then stays paused
+    pub fn advance_frame(&mut self) {
+        self.paused = true;
+        self.advance = true;
+    }
+
+    fn uncapped(&self) -> bool {
+        self.fast_forward && self.fast_forward_speed == 0
+    }
+    fn current_frame_time(&self) -> Duration {
+        if self.fast_forward {
+            self.frame_time / self.fast_forward_speed.max(1)
+        } else {
+            self.frame_time
+        }
+    }
+
+    /// whether a frame should be ran right now. when this returns true
+    /// the frame is counted as ran
+    pub fn frame_due(&mut self, now: Instant) -> bool {
+        if self.paused {
+            return std::mem::take(&mut self.advance);
+        }
+        if self.uncapped() {
+            self.next_frame = now;
+            return true;
+        }
+        if now < self.next_frame {
+            return false;
+        }
+
+        let frame_time = self.current_frame_time();
+        self.next_frame += frame_time;
+        if now > self.next_frame + frame_time * MAX_FRAMES_BEHIND {
+            self.next_frame = now + frame_time;
+        }
+        true
+    }
+
+    /// when the event loop should wake back up, none means straight away
+    pub fn wake_at(&self) -> Option<Instant> {
+        if self.uncapped() || (self.paused && self.advance) {
+            return None;
+        }
+        if self.paused {
+            // nothing to do until a key is pressed, so check in every so often
+            return Some(Instant::now() + self.frame_time);
+        }
+        Some(self.next_frame)
+    }
+}
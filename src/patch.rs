@@ -0,0 +1,349 @@
+use crate::png::crc32;
+
+/// applies an IPS, UPS or BPS patch to a rom, working out which from its header.
+///
+/// UPS and BPS patches carry CRCs of the rom they were made against, the
+/// rom they make and of themselves, all of which have to match. IPS has
+/// nothing to check so it is applied as is
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.starts_with(b"PATCH") {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(b"UPS1") {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(rom, patch)
+    } else {
+        Err("not an IPS, UPS or BPS patch".to_string())
+    }
+}
+
+/// reads through the patch, running out of it is always an error
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.data.get(self.pos).ok_or("the patch ends early")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or("the patch ends early")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn big_endian(&mut self, len: usize) -> Result<usize, String> {
+        Ok(self.bytes(len)?.iter().fold(0, |n, b| (n << 8) | *b as usize))
+    }
+    /// the variable length numbers UPS and BPS use, 7 bits at a time with
+    /// the top bit marking the last byte. each extra byte also adds one to
+    /// the rest so there is only one way of writing every number
+    fn number(&mut self) -> Result<usize, String> {
+        let (mut value, mut shift) = (0usize, 1usize);
+        loop {
+            let byte = self.byte()?;
+            value = value.checked_add((byte & 0x7F) as usize * shift).ok_or("invalid number in the patch")?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).ok_or("invalid number in the patch")?;
+            value += shift;
+        }
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = rom.to_vec();
+    let mut reader = Reader { data: patch, pos: 5 };
+    loop {
+        if reader.data[reader.pos..].starts_with(b"EOF") {
+            reader.pos += 3;
+            break;
+        }
+        let offset = reader.big_endian(3)?;
+        let len = reader.big_endian(2)?;
+        // a length of 0 means a run of the same byte
+        let data = match len {
+            0 => {
+                let count = reader.big_endian(2)?;
+                vec![reader.byte()?; count]
+            }
+            _ => reader.bytes(len)?.to_vec(),
+        };
+        if out.len() < offset + data.len() {
+            out.resize(offset + data.len(), 0);
+        }
+        out[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    // some patches shrink the rom, the new size comes after the EOF
+    if patch.len() - reader.pos == 3 {
+        out.truncate(reader.big_endian(3)?);
+    }
+    Ok(out)
+}
+
+/// the three CRCs at the end of a UPS or BPS patch
+fn check_footer<'a>(rom: &[u8], patch: &'a [u8]) -> Result<(u32, &'a [u8]), String> {
+    if patch.len() < 16 {
+        return Err("the patch is too short".to_string());
+    }
+    let (body, footer) = patch.split_at(patch.len() - 12);
+    let crc = |i: usize| u32::from_le_bytes(footer[i..i + 4].try_into().unwrap());
+    if crc32(&patch[..patch.len() - 4]) != crc(8) {
+        return Err("the patch is corrupt, its crc doesnt match".to_string());
+    }
+    if crc32(rom) != crc(0) {
+        return Err("the patch was made for a different rom".to_string());
+    }
+    Ok((crc(4), body))
+}
+
+fn check_output(out: &[u8], expected: u32) -> Result<(), String> {
+    if crc32(out) != expected {
+        return Err("the patched rom's crc doesnt match".to_string());
+    }
+    Ok(())
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let (output_crc, body) = check_footer(rom, patch)?;
+    let mut reader = Reader { data: body, pos: 4 };
+    let _input_size = reader.number()?;
+    let output_size = reader.number()?;
+
+    let mut out = rom.to_vec();
+    out.resize(output_size, 0);
+    let mut pos = 0;
+    while reader.pos < body.len() {
+        pos += reader.number()?;
+        // bytes are xored with the rom until a 0, which still counts as a byte
+        loop {
+            let xor = reader.byte()?;
+            if let Some(byte) = out.get_mut(pos) {
+                *byte ^= xor;
+            }
+            pos += 1;
+            if xor == 0 {
+                break;
+            }
+        }
+    }
+    check_output(&out, output_crc)?;
+    Ok(out)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let (output_crc, body) = check_footer(rom, patch)?;
+    let mut reader = Reader { data: body, pos: 4 };
+    let _source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+
+    let mut out = Vec::with_capacity(target_size);
+    // the copy commands move these around relative to where they were
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+    let relative = |offset: usize, data: usize| -> Result<usize, String> {
+        let moved = data >> 1;
+        let result = if data & 1 != 0 { offset.checked_sub(moved) } else { offset.checked_add(moved) };
+        result.ok_or("the patch copies from outside the rom".to_string())
+    };
+    while reader.pos < body.len() {
+        let data = reader.number()?;
+        let len = (data >> 2) + 1;
+        match data & 0b11 {
+            // the rom's bytes at the same place
+            0 => {
+                let start = out.len();
+                out.extend_from_slice(rom.get(start..start + len).ok_or("the patch reads past the end of the rom")?);
+            }
+            // new bytes from the patch
+            1 => out.extend_from_slice(reader.bytes(len)?),
+            // bytes from anywhere in the rom
+            2 => {
+                source_offset = relative(source_offset, reader.number()?)?;
+                out.extend_from_slice(rom.get(source_offset..source_offset + len).ok_or("the patch reads past the end of the rom")?);
+                source_offset += len;
+            }
+            // bytes already written, one at a time since they can overlap
+            _ => {
+                target_offset = relative(target_offset, reader.number()?)?;
+                for _ in 0..len {
+                    let byte = *out.get(target_offset).ok_or("the patch copies from outside the rom")?;
+                    out.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    if out.len() != target_size {
+        return Err("the patched rom is the wrong size".to_string());
+    }
+    check_output(&out, output_crc)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the opposite of `Reader::number`
+    fn number(mut n: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        loop {
+            let bits = (n & 0x7F) as u8;
+            n >>= 7;
+            if n == 0 {
+                out.push(0x80 | bits);
+                return out;
+            }
+            out.push(bits);
+            n -= 1;
+        }
+    }
+
+    /// the crcs of the rom, the patched rom and then the patch itself
+    fn with_footer(mut patch: Vec<u8>, rom: &[u8], out: &[u8]) -> Vec<u8> {
+        patch.extend(crc32(rom).to_le_bytes());
+        patch.extend(crc32(out).to_le_bytes());
+        let crc = crc32(&patch);
+        patch.extend(crc.to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn numbers() {
+        for (bytes, expected) in [(&[0x80][..], 0), (&[0xFF], 127), (&[0x00, 0x80], 128), (&[0x7F, 0x80], 255), (&[0x00, 0x81], 256)] {
+            assert_eq!(Reader { data: bytes, pos: 0 }.number(), Ok(expected));
+        }
+        for n in [0, 1, 127, 128, 300, 16511, 16512, 1 << 20] {
+            assert_eq!(Reader { data: &number(n), pos: 0 }.number(), Ok(n));
+        }
+        assert!(Reader { data: &[0x00, 0x01], pos: 0 }.number().is_err());
+    }
+
+    #[test]
+    fn unknown_patch() {
+        assert!(apply(b"rom", b"NOTAPATCH").is_err());
+    }
+
+    #[test]
+    fn ips_record() {
+        let patch = [&b"PATCH"[..], &[0x00, 0x00, 0x01, 0x00, 0x02], b"xy", b"EOF"].concat();
+        assert_eq!(apply(b"ABCD", &patch).unwrap(), b"AxyD");
+    }
+
+    #[test]
+    fn ips_run() {
+        // a length of 0, then 3 of 'z'
+        let patch = [&b"PATCH"[..], &[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03], b"z", b"EOF"].concat();
+        assert_eq!(apply(b"ABCDE", &patch).unwrap(), b"AzzzE");
+    }
+
+    #[test]
+    fn ips_grows_the_rom() {
+        let patch = [&b"PATCH"[..], &[0x00, 0x00, 0x06, 0x00, 0x01], b"!", b"EOF"].concat();
+        assert_eq!(apply(b"ABCD", &patch).unwrap(), b"ABCD\0\0!");
+    }
+
+    #[test]
+    fn ips_truncate() {
+        let patch = [&b"PATCH"[..], b"EOF", &[0x00, 0x00, 0x02]].concat();
+        assert_eq!(apply(b"ABCD", &patch).unwrap(), b"AB");
+    }
+
+    #[test]
+    fn ips_without_eof() {
+        let patch = [&b"PATCH"[..], &[0x00, 0x00, 0x01, 0x00, 0x02], b"xy"].concat();
+        assert!(apply(b"ABCD", &patch).is_err());
+        let patch = [&b"PATCH"[..], &[0x00, 0x00, 0x01, 0x00, 0x05], b"xy", b"EOF"].concat();
+        assert!(apply(b"ABCD", &patch).is_err());
+    }
+
+    fn ups_patch(rom: &[u8], out: &[u8]) -> Vec<u8> {
+        let mut patch = b"UPS1".to_vec();
+        patch.extend(number(rom.len()));
+        patch.extend(number(out.len()));
+        // H ^ J at 0, then skip to 5 and xor in the new '!'
+        patch.extend(number(0));
+        patch.extend([b'H' ^ b'J', 0]);
+        patch.extend(number(3));
+        patch.extend([b'!', 0]);
+        with_footer(patch, rom, out)
+    }
+
+    #[test]
+    fn ups() {
+        let patch = ups_patch(b"HELLO", b"JELLO!");
+        assert_eq!(apply(b"HELLO", &patch).unwrap(), b"JELLO!");
+    }
+
+    #[test]
+    fn ups_crc_mismatches() {
+        let patch = ups_patch(b"HELLO", b"JELLO!");
+        assert_eq!(apply(b"HULLO", &patch), Err("the patch was made for a different rom".to_string()));
+
+        let mut corrupt = patch.clone();
+        corrupt[6] ^= 1;
+        assert_eq!(apply(b"HELLO", &corrupt), Err("the patch is corrupt, its crc doesnt match".to_string()));
+
+        // a valid patch whose output crc is for something else
+        let wrong = with_footer(patch[..patch.len() - 12].to_vec(), b"HELLO", b"JELLO?");
+        assert_eq!(apply(b"HELLO", &wrong), Err("the patched rom's crc doesnt match".to_string()));
+    }
+
+    fn bps_command(kind: usize, len: usize) -> Vec<u8> {
+        number((len - 1) << 2 | kind)
+    }
+
+    #[test]
+    fn bps() {
+        let rom = b"ABCDEFGH";
+        let out = b"ABCxyGHDEEEEE";
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(number(rom.len()));
+        patch.extend(number(out.len()));
+        patch.extend(number(2));
+        patch.extend(b"{}");
+        // ABC from the rom where it is
+        patch.extend(bps_command(0, 3));
+        // xy from the patch
+        patch.extend(bps_command(1, 2));
+        patch.extend(b"xy");
+        // GH from 6 in the rom
+        patch.extend(bps_command(2, 2));
+        patch.extend(number(6 << 1));
+        // DE from 3, 5 back from where the last copy ended
+        patch.extend(bps_command(2, 2));
+        patch.extend(number(5 << 1 | 1));
+        // the E just written, copied over itself 4 times
+        patch.extend(bps_command(3, 4));
+        patch.extend(number(8 << 1));
+        let patch = with_footer(patch, rom, out);
+        assert_eq!(apply(rom, &patch).unwrap(), out);
+    }
+
+    #[test]
+    fn bps_errors() {
+        let rom = b"ABCD";
+        let build = |commands: &[u8], out: &[u8]| {
+            let mut patch = b"BPS1".to_vec();
+            patch.extend(number(rom.len()));
+            patch.extend(number(out.len()));
+            patch.extend(number(0));
+            patch.extend(commands);
+            with_footer(patch, rom, out)
+        };
+        // copying from before the start of the rom
+        let commands = [bps_command(2, 1), number(1 << 1 | 1)].concat();
+        assert_eq!(apply(rom, &build(&commands, b"A")), Err("the patch copies from outside the rom".to_string()));
+        // reading past the end of the rom
+        let commands = [bps_command(2, 2), number(3 << 1)].concat();
+        assert!(apply(rom, &build(&commands, b"DE")).is_err());
+        // fewer bytes than it said it would make
+        let commands = bps_command(0, 2);
+        assert_eq!(apply(rom, &build(&commands, b"ABC")), Err("the patched rom is the wrong size".to_string()));
+    }
+}
@@ -0,0 +1,639 @@
+use std::io::{Write, Seek, SeekFrom};
+
+/// just enough of the PNG format to store and load screenshots of the
+/// gameboy's screen. Images are always handed around as RGBA bytes.
+///
+/// the encoder only does the simple kind of compression, which is plenty
+/// for the gameboy's flat colours, however the decoder has to inflate
+/// properly as reference images come from other emulators and tools.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// the standard CRC-32 used by PNG (and zip, and the blargg tests)
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+/// allows the crc to be calculated over multiple slices
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32_update(crc32(kind), data);
+    out.extend(crc.to_be_bytes());
+}
+
+/// bits go in lowest first, the same order `BitReader` takes them out
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u32,
+    count: u8,
+}
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u8) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+    /// huffman codes are the other way round, starting from their top bit
+    fn code(&mut self, code: u32, length: u8) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+        }
+        self.out
+    }
+}
+
+// how far back and how hard the compressor looks for repeats
+const WINDOW: usize = 32768;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+/// a literal byte, a length (257-285) or the end of the block (256) in the fixed codes
+fn fixed_literal(writer: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.code(0x30 + symbol, 8),
+        144..=255 => writer.code(0x190 + symbol - 144, 9),
+        256..=279 => writer.code(symbol - 256, 7),
+        _ => writer.code(0xC0 + symbol - 280, 8),
+    }
+}
+
+/// a single deflate block using the fixed huffman codes, with repeats found
+/// through chains of earlier positions that started with the same 3 bytes.
+/// gameboy frames are mostly runs and repeated tiles so this gets most of
+/// the way there without building tables for every image
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    // the last block, with fixed codes
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let hash = |i: usize| {
+        let key = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+        (key.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+    };
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if i + 3 <= data.len() {
+            let longest = (data.len() - i).min(258);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let length = (0..longest).take_while(|k| data[candidate + k] == data[i + k]).count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, i - candidate);
+                    if length == longest {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        let step = if best_length >= 3 {
+            let index = LENGTH_BASE.iter().rposition(|base| *base as usize <= best_length).unwrap();
+            fixed_literal(&mut writer, 257 + index as u16);
+            writer.bits((best_length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index]);
+            let index = DIST_BASE.iter().rposition(|base| *base as usize <= best_distance).unwrap();
+            writer.code(index as u32, 5);
+            writer.bits((best_distance - DIST_BASE[index] as usize) as u32, DIST_EXTRA[index]);
+            best_length
+        } else {
+            fixed_literal(&mut writer, data[i] as u16);
+            1
+        };
+        // every position passed over can be repeated later, up to the last full 3 bytes
+        let end = (i + step).min(data.len().saturating_sub(2));
+        for (j, previous) in previous.iter_mut().enumerate().take(end).skip(i) {
+            let h = hash(j);
+            *previous = head[h];
+            head[h] = j;
+        }
+        i += step;
+    }
+    fixed_literal(&mut writer, 256);
+    writer.finish()
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// the IHDR data for an 8-bit RGBA image
+fn header(width: u32, height: u32) -> Vec<u8> {
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend(width.to_be_bytes());
+    ihdr.extend(height.to_be_bytes());
+    ihdr.extend([8, 6, 0, 0, 0]);
+    ihdr
+}
+/// every row is given the `None` filter
+fn filtered_rows(width: u32, rgba: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(rgba.len() + rgba.len() / (width as usize * 4).max(1));
+    for row in rgba.chunks(width as usize * 4) {
+        raw.push(0);
+        raw.extend(row);
+    }
+    raw
+}
+
+pub fn encode(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    if rgba.len() != (width * height * 4) as usize {
+        panic!("image data doesnt match its size");
+    }
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header(width, height));
+    write_chunk(&mut out, b"IDAT", &zlib(&filtered_rows(width, rgba)));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// an animated png, written out a frame at a time. the frame count comes
+/// before the frames so `finish` goes back and fills it in
+pub struct Apng<W: Write + Seek> {
+    out: W,
+    width: u32,
+    height: u32,
+    frames: u32,
+    // fcTL and fdAT chunks share the one sequence
+    sequence: u32,
+}
+
+// the acTL chunk comes straight after the signature and IHDR
+const ACTL_POSITION: u64 = 8 + 12 + 13;
+
+impl<W: Write + Seek> Apng<W> {
+    pub fn new(mut out: W, width: u32, height: u32) -> std::io::Result<Self> {
+        let mut head = SIGNATURE.to_vec();
+        write_chunk(&mut head, b"IHDR", &header(width, height));
+        write_chunk(&mut head, b"acTL", &animation_control(0));
+        out.write_all(&head)?;
+        Ok(Self { out, width, height, frames: 0, sequence: 0 })
+    }
+
+    /// shows the frame for `delay` milliseconds
+    pub fn frame(&mut self, rgba: &[u8], delay: u16) -> std::io::Result<()> {
+        if rgba.len() != (self.width * self.height * 4) as usize {
+            panic!("frame doesnt match the animation's size");
+        }
+        // the whole image every time, replacing what was there
+        let mut control = Vec::with_capacity(26);
+        control.extend(self.sequence.to_be_bytes());
+        control.extend(self.width.to_be_bytes());
+        control.extend(self.height.to_be_bytes());
+        control.extend([0; 8]);
+        control.extend(delay.to_be_bytes());
+        control.extend(1000u16.to_be_bytes());
+        control.extend([0, 0]);
+        self.sequence += 1;
+
+        let mut out = Vec::new();
+        write_chunk(&mut out, b"fcTL", &control);
+        let data = zlib(&filtered_rows(self.width, rgba));
+        // the first frame doubles as the still image for viewers without apng
+        if self.frames == 0 {
+            write_chunk(&mut out, b"IDAT", &data);
+        } else {
+            let mut chunk = self.sequence.to_be_bytes().to_vec();
+            chunk.extend(data);
+            write_chunk(&mut out, b"fdAT", &chunk);
+            self.sequence += 1;
+        }
+        self.frames += 1;
+        self.out.write_all(&out)
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let mut end = Vec::new();
+        write_chunk(&mut end, b"IEND", &[]);
+        self.out.write_all(&end)?;
+        let mut actl = Vec::new();
+        write_chunk(&mut actl, b"acTL", &animation_control(self.frames));
+        self.out.seek(SeekFrom::Start(ACTL_POSITION))?;
+        self.out.write_all(&actl)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// the frame count, then how many times to play it with 0 looping forever
+fn animation_control(frames: u32) -> Vec<u8> {
+    let mut data = frames.to_be_bytes().to_vec();
+    data.extend(0u32.to_be_bytes());
+    data
+}
+
+pub fn decode(data: &[u8]) -> Result<Image, String> {
+    if data.len() < 8 || data[0..8] != SIGNATURE {
+        return Err("not a png file".to_string());
+    }
+    let mut pos = 8;
+    let mut ihdr: Option<[u8; 13]> = None;
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut transparency: Vec<u8> = Vec::new();
+    let mut compressed = Vec::new();
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[pos..pos+4].try_into().unwrap()) as usize;
+        let kind = &data[pos+4..pos+8];
+        let Some(body) = data.get(pos+8..pos+8+len) else {
+            return Err("truncated chunk".to_string());
+        };
+        match kind {
+            b"IHDR" => ihdr = body.try_into().ok(),
+            b"PLTE" => palette = body.chunks_exact(3).map(|c| [c[0], c[1], c[2], 255]).collect(),
+            b"tRNS" => transparency = body.to_vec(),
+            b"IDAT" => compressed.extend(body),
+            b"IEND" => break,
+            _ => (),
+        }
+        // skip over the crc as well
+        pos += 12 + len;
+    }
+    for (i, alpha) in transparency.iter().enumerate() {
+        if let Some(entry) = palette.get_mut(i) {
+            entry[3] = *alpha;
+        }
+    }
+
+    let Some(ihdr) = ihdr else {
+        return Err("missing IHDR chunk".to_string());
+    };
+    let width = u32::from_be_bytes(ihdr[0..4].try_into().unwrap());
+    let height = u32::from_be_bytes(ihdr[4..8].try_into().unwrap());
+    let (depth, color_type, interlace) = (ihdr[8], ihdr[9], ihdr[12]);
+    if interlace != 0 {
+        return Err("interlaced pngs are not supported".to_string());
+    }
+    let channels = match color_type {
+        0 => 1, // greyscale
+        2 => 3, // rgb
+        3 => 1, // palette
+        4 => 2, // greyscale + alpha
+        6 => 4, // rgba
+        _ => return Err(format!("invalid colour type {color_type}")),
+    };
+    if compressed.len() < 2 {
+        return Err("missing image data".to_string());
+    }
+    // the first two bytes are the zlib header
+    let raw = inflate(&compressed[2..])?;
+
+    let bits_per_pixel = channels * depth as usize;
+    let stride = (width as usize * bits_per_pixel).div_ceil(8);
+    let bpp = bits_per_pixel.div_ceil(8);
+    if raw.len() < (stride + 1) * height as usize {
+        return Err("not enough image data".to_string());
+    }
+
+    let mut rows: Vec<Vec<u8>> = Vec::with_capacity(height as usize);
+    let mut previous = vec![0; stride];
+    for y in 0..height as usize {
+        let filter = raw[y * (stride + 1)];
+        let mut row = raw[y*(stride+1)+1..(y+1)*(stride+1)].to_vec();
+        unfilter(filter, &mut row, &previous, bpp)?;
+        previous = row.clone();
+        rows.push(row);
+    }
+
+    let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+    for row in &rows {
+        for x in 0..width as usize {
+            // pulls out the sample at the index, only keeping the top 8 bits of it
+            let sample = |i: usize| -> u8 {
+                match depth {
+                    8 => row[i],
+                    16 => row[i * 2],
+                    _ => {
+                        let bit = i * depth as usize;
+                        let value = (row[bit / 8] >> (8 - depth as usize - bit % 8)) & ((1 << depth) - 1);
+                        if color_type == 3 { value } else { value * (255 / ((1 << depth) - 1)) }
+                    }
+                }
+            };
+            let i = x * channels;
+            let pixel = match color_type {
+                0 => { let v = sample(i); [v, v, v, 255] }
+                2 => [sample(i), sample(i+1), sample(i+2), 255],
+                3 => match palette.get(sample(i) as usize) {
+                    Some(entry) => *entry,
+                    None => return Err("palette index out of range".to_string()),
+                },
+                4 => { let v = sample(i); [v, v, v, sample(i+1)] }
+                _ => [sample(i), sample(i+1), sample(i+2), sample(i+3)],
+            };
+            rgba.extend(pixel);
+        }
+    }
+    Ok(Image { width, height, rgba })
+}
+
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> Result<(), String> {
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up = previous[i];
+        let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => {
+                // paeth predictor
+                let p = left as i16 + up as i16 - up_left as i16;
+                let (pa, pb, pc) = ((p - left as i16).abs(), (p - up as i16).abs(), (p - up_left as i16).abs());
+                if pa <= pb && pa <= pc { left } else if pb <= pc { up } else { up_left }
+            }
+            _ => return Err(format!("invalid filter type {filter}")),
+        };
+        row[i] = row[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+/// reads a deflate stream one bit at a time, lowest bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+impl BitReader<'_> {
+    fn bits(&mut self, count: u8) -> Result<u16, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let Some(byte) = self.data.get(self.pos) else {
+                return Err("deflate stream ended early".to_string());
+            };
+            value |= (((byte >> self.bit) & 1) as u16) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// canonical huffman codes, stored as the number of codes of each length
+/// and the symbols sorted by their code
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..16 {
+            for (symbol, l) in lengths.iter().enumerate() {
+                if *l == length {
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+        Self { counts, symbols }
+    }
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid huffman code".to_string())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// the order the code length code lengths are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader { data, pos: 0, bit: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let Some(header) = data.get(reader.pos..reader.pos+4) else {
+                    return Err("deflate stream ended early".to_string());
+                };
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let start = reader.pos + 4;
+                let Some(block) = data.get(start..start+len) else {
+                    return Err("deflate stream ended early".to_string());
+                };
+                out.extend(block);
+                reader.pos = start + len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[0..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..288].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            _ => return Err("invalid deflate block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn read_dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for i in CODE_LENGTH_ORDER.iter().take(code_count) {
+        code_lengths[*i] = reader.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_lengths.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Some(previous) => (*previous, 3 + reader.bits(2)?),
+                None => return Err("repeat with no previous length".to_string()),
+            },
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err("too many code lengths".to_string());
+    }
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)?;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = (symbol - 257) as usize;
+                if index >= 29 {
+                    return Err("invalid length symbol".to_string());
+                }
+                let length = LENGTH_BASE[index] + reader.bits(LENGTH_EXTRA[index])?;
+                let index = distances.decode(reader)? as usize;
+                if index >= 30 {
+                    return Err("invalid distance symbol".to_string());
+                }
+                let distance = (DIST_BASE[index] + reader.bits(DIST_EXTRA[index])?) as usize;
+                if distance > out.len() {
+                    return Err("distance goes back too far".to_string());
+                }
+                // copied byte by byte since the copy can overlap itself
+                let start = out.len() - distance;
+                for i in 0..length as usize {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a simple generator so the "random" data is the same every run
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x1234_5678u32;
+        (0..len).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }).collect()
+    }
+
+    #[test]
+    fn deflate_round_trip() {
+        let mut tiles = Vec::new();
+        for i in 0..4000 {
+            tiles.extend([0xFF, 0x00, (i % 7) as u8, 0x3C]);
+        }
+        // repeats further back than the window can reach have to be written out again
+        let far = [noise(20000), noise(20000)].concat();
+        for data in [vec![], vec![7], b"ab".to_vec(), b"abcabcabcabc".to_vec(), vec![0; 1000], tiles, noise(5000), far] {
+            let compressed = deflate(&data);
+            assert_eq!(inflate(&compressed), Ok(data.clone()), "{} bytes", data.len());
+        }
+        assert!(deflate(&vec![0; 10000]).len() < 100);
+    }
+
+    #[test]
+    fn inflate_stored_and_dynamic_blocks() {
+        // a stored block which isnt the last, then an empty fixed one which is
+        let stored = [0x00, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c', 0x03, 0x00];
+        assert_eq!(inflate(&stored), Ok(b"abc".to_vec()));
+
+        // made by zlib at level 9, which picked its own huffman tables
+        let dynamic = [
+            0x25, 0x8A, 0x81, 0x09, 0x00, 0x40, 0x10, 0x82, 0x66, 0xD5, 0xDA, 0x7F, 0x86, 0xBF, 0xF8, 0xA0, 0x40, 0x09,
+            0x05, 0x2F, 0x48, 0x37, 0x44, 0x3B, 0x2C, 0x5F, 0x05, 0x33, 0x7B, 0xA5, 0xBD, 0x6F, 0xC8, 0x44, 0x7C,
+        ];
+        let expected = b"abbaabbbbabadbabaacbbdbbabdaabadbacabcaacbaacaddabbcaccaaccb";
+        assert_eq!(inflate(&dynamic), Ok(expected.to_vec()));
+    }
+
+    #[test]
+    fn inflate_errors() {
+        let compressed = deflate(b"some text to cut short, some text to cut short");
+        assert!(inflate(&compressed[..compressed.len() / 2]).is_err());
+        assert!(inflate(&[0x07]).is_err());
+        assert!(inflate(&[0x00, 0x05, 0x00, 0xFA, 0xFF, b'a']).is_err());
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let (width, height) = (37, 11);
+        let rgba: Vec<u8> = noise(width * height * 4);
+        let png = encode(width as u32, height as u32, &rgba);
+        let image = decode(&png).unwrap();
+        assert_eq!((image.width, image.height), (width as u32, height as u32));
+        assert_eq!(image.rgba, rgba);
+
+        assert!(decode(&png[..png.len() / 2]).is_err());
+        assert!(decode(b"not a png").is_err());
+    }
+}
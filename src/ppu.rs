@@ -28,9 +28,9 @@ impl Ppu {
     }
 
     /// once again just simplifies reading memory in other functions
-    /// easier to call `self.read_memory(address)` rather than `self.memory.borrow().read(address)`
+    /// easier to call `self.read_memory(address)` rather than `self.memory.borrow().peek(address)`
     fn read_memory(&self, address: u16) -> u8 {
-        self.memory.borrow().read(address)
+        self.memory.borrow().peek(address)
     }
 
     /// this function should always take 80 T-cycles
@@ -56,11 +56,13 @@ impl Ppu {
         }
     }
     /// this transfers pixels to the LCD. The timings for the function can change though
-    pub fn draw_scanline(&mut self) -> Vec<u8> {
+    /// dmg pixels are shades and cgb pixels colours, see `pallete_to_rgba`
+    pub fn draw_scanline(&mut self) -> Vec<u16> {
         let lcdc = self.read_memory(PpuRegister::LCDC as u16);
-        // the screen is off
+        let cgb = self.memory.borrow().is_cgb();
+        // the screen is off, which is white either way
         if (lcdc & 0b1000_0000) == 0 {
-            return  vec![0; 160];
+            return vec![if cgb { 0xFFFF } else { 0 }; 160];
         }
         self.oam_scan();
         self.scanline_buffer.sort_by(|a, b| a[1].cmp(&b[1]));
@@ -73,6 +75,7 @@ impl Ppu {
         let background_pallete = self.read_memory(PpuRegister::BGP as u16);
         let background_index = (lcdc & 0b0000_1000) >> 3;
         let background_map = self.memory.borrow().read_map(background_index);
+        let attributes = self.memory.borrow().read_attributes(background_index);
 
         let mut new_scanline = Vec::new();
 
@@ -86,27 +89,42 @@ impl Ppu {
 
             let background_tile_index = (background_tile_y as u16)*32 + background_tile_x as u16; // the tiles index in the map area
             let tile_index = background_map[background_tile_index as usize];
-            let tile = self.memory.borrow().read_tile(tile_index); // the tile
+            // always zero on the dmg, so nothing gets flipped or banked
+            let attribute = attributes[background_tile_index as usize];
+            let bank = (attribute >> 3) & 1;
+            let tile = self.memory.borrow().read_tile_in_bank(0x8000 + (tile_index as u16)*16, bank); // the tile
 
-            let tile_inner_row = background_pos_y%8;
+            let mut tile_inner_row = background_pos_y%8;
+            if attribute & 0b0100_0000 != 0 {
+                tile_inner_row = 7 - tile_inner_row;
+            }
             
             let row_data = tile[tile_inner_row as usize];
+            // the leftmost pixel is in the top bits, unless the tile is flipped
+            let order: [u16; 8] = if attribute & 0b0010_0000 != 0 { [0, 1, 2, 3, 4, 5, 6, 7] } else { [7, 6, 5, 4, 3, 2, 1, 0] };
             // add the pallete to this
-            for i in (0..8).rev() {
-                let pallete_index = (row_data >> (i*2)) & 0b0000_0000_0000_0011;
-                let real_color = (background_pallete >> (pallete_index*2)) & 0b0000_0011;
+            for i in order {
+                let pallete_index = ((row_data >> (i*2)) & 0b0000_0000_0000_0011) as u8;
+                let real_color = match &self.memory.borrow().cgb {
+                    Some(cgb) => 0x8000 | cgb.bg_colour(attribute & 0b111, pallete_index),
+                    None => ((background_pallete >> (pallete_index*2)) & 0b0000_0011) as u16,
+                };
                 new_scanline.push(real_color)
             }
 
             // now get the oam stuff
             
         }
+        // 21 tiles are drawn so that scrolling can start part way through the first
+        // tile, only 160 pixels actually make it onto the screen
+        new_scanline.drain(0..(scroll_x % 8) as usize);
+        new_scanline.truncate(160);
         
         // handle the interrupt(s)
+        // the coincidence flag itself is kept up to date in STAT by the gameboy, see `update_ly`
         if self.read_memory(0xFF44) == self.read_memory(0xFF45) {
-            self.memory.borrow_mut().write(PpuRegister::STAT as u16, 0b0100_0000);
             let if_interrupt = self.read_memory(0xFF0F);
-            self.memory.borrow_mut().write(0xFF0f, if_interrupt|0b0000_0010);
+            self.memory.borrow_mut().unchecked_write(0xFF0F, if_interrupt|0b0000_0010);
         }
         // i dont bother alerting the ppu modes as my emulator doesnt implement them normally
         new_scanline
@@ -0,0 +1,208 @@
+use crate::link::Cable;
+use crate::serial::Message;
+use crate::framebuffer::Framebuffer;
+
+const INIT: u8 = 0x01;
+const PRINT: u8 = 0x02;
+const DATA: u8 = 0x04;
+const STATUS: u8 = 0x0F;
+
+// the status bits
+const CHECKSUM_ERROR: u8 = 0b0000_0001;
+const PRINTING: u8 = 0b0000_0010;
+const DATA_FULL: u8 = 0b0000_0100;
+const UNPROCESSED: u8 = 0b0000_1000;
+
+// the printer's memory holds 9 strips of 20x2 tiles
+const BUFFER_SIZE: usize = 0x2000;
+// how many status checks a print stays busy for, games wait for it to finish
+const PRINT_POLLS: u8 = 4;
+const WIDTH: usize = 160;
+
+/// the game boy printer, plugged in instead of another gameboy.
+///
+/// games send it packets of `0x88 0x33 command compression length data checksum`
+/// followed by two 0s, which the printer answers with 0x81 and then its status.
+/// DATA packets fill the printer's memory with tiles, 20 to a row, which
+/// PRINT then puts onto the page. the page is written out as a png once a
+/// print feeds the paper on after itself, so pictures printed in parts end
+/// up as one image
+pub struct Printer {
+    dir: String,
+    // the packet coming in, from the magic bytes on
+    packet: Vec<u8>,
+    // tiles waiting to be printed
+    buffer: Vec<u8>,
+    status: u8,
+    busy: u8,
+    // the picture printed so far as dmg shades, WIDTH pixels a row
+    page: Vec<u16>,
+    pages: u32,
+}
+
+impl Printer {
+    /// the pages are written into `dir`, which is made if it has to be
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: dir.to_string(),
+            packet: Vec::new(),
+            buffer: Vec::new(),
+            status: 0,
+            busy: 0,
+            page: Vec::new(),
+            pages: 0,
+        }
+    }
+
+    /// takes a byte from the game boy and returns the one shifted back
+    pub fn receive(&mut self, byte: u8) -> u8 {
+        // anything before the magic bytes is ignored
+        match (self.packet.len(), byte) {
+            (0, 0x88) | (1, 0x33) => {}
+            (0 | 1, _) => {
+                self.packet.clear();
+                return 0x00;
+            }
+            _ => {}
+        }
+        self.packet.push(byte);
+        let position = self.packet.len() - 1;
+        if self.packet.len() < 6 {
+            return 0x00;
+        }
+        // where the data ends and the checksum starts
+        let end = 6 + u16::from_le_bytes([self.packet[4], self.packet[5]]) as usize;
+        match position {
+            p if p == end + 1 => {
+                self.run();
+                0x00
+            }
+            p if p == end + 2 => 0x81,
+            p if p == end + 3 => {
+                self.packet.clear();
+                self.status()
+            }
+            _ => 0x00,
+        }
+    }
+
+    fn status(&mut self) -> u8 {
+        let mut status = self.status;
+        if self.busy > 0 {
+            status |= PRINTING;
+        }
+        if !self.buffer.is_empty() {
+            status |= UNPROCESSED;
+        }
+        if self.buffer.len() >= BUFFER_SIZE {
+            status |= DATA_FULL;
+        }
+        status
+    }
+
+    /// the whole packet up to its checksum has arrived
+    fn run(&mut self) {
+        let body = &self.packet[2..self.packet.len() - 2];
+        let checksum = body.iter().fold(0u16, |sum, b| sum.wrapping_add(*b as u16));
+        let expected = u16::from_le_bytes([self.packet[self.packet.len() - 2], self.packet[self.packet.len() - 1]]);
+        self.status &= !CHECKSUM_ERROR;
+        if checksum != expected {
+            self.status |= CHECKSUM_ERROR;
+            return;
+        }
+        let (command, compressed, data) = (body[0], body[1] & 1 != 0, body[4..].to_vec());
+        match command {
+            INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.busy = 0;
+            }
+            // an empty one just marks the end of the data
+            DATA => {
+                let data = if compressed { decompress(&data) } else { data };
+                let room = BUFFER_SIZE.saturating_sub(self.buffer.len());
+                self.buffer.extend(data.into_iter().take(room));
+            }
+            PRINT if data.len() >= 4 => self.print(data[0], data[1], data[2]),
+            STATUS => self.busy = self.busy.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    /// the margins are in feeds before and after the picture, no sheets only feeds the paper
+    fn print(&mut self, sheets: u8, margins: u8, palette: u8) {
+        // some games leave the palette empty meaning the normal one
+        let palette = if palette == 0 { 0b1110_0100 } else { palette };
+        let tiles = std::mem::take(&mut self.buffer);
+        self.busy = PRINT_POLLS;
+        if sheets == 0 {
+            return;
+        }
+        for row in tiles.chunks_exact(20 * 16) {
+            for y in 0..8 {
+                for x in 0..WIDTH {
+                    let tile = &row[(x / 8) * 16..];
+                    let bit = 7 - (x % 8);
+                    let colour = (tile[y * 2] >> bit) & 1 | ((tile[y * 2 + 1] >> bit) & 1) << 1;
+                    self.page.push(((palette >> (colour * 2)) & 0b11) as u16);
+                }
+            }
+        }
+        if margins & 0x0F != 0 {
+            self.finish_page();
+        }
+    }
+
+    /// writes the page out as `<dir>/print<n>.png`
+    fn finish_page(&mut self) {
+        if self.page.is_empty() {
+            return;
+        }
+        let page = std::mem::take(&mut self.page);
+        self.pages += 1;
+        let height = (page.len() / WIDTH) as u32;
+        let path = std::path::Path::new(&self.dir).join(format!("print{:03}.png", self.pages));
+        let path = path.to_string_lossy();
+        let written = std::fs::create_dir_all(&self.dir).map_err(|e| format!("could not make {}: {e}", self.dir))
+            .and_then(|_| Framebuffer::new(WIDTH as u32, height, page).save_png(&path, 1));
+        match written {
+            Err(e) => println!("{e}"),
+            Ok(_) => println!("printed {path}"),
+        }
+    }
+}
+
+/// a control byte with the top bit set repeats the next byte (control & 0x7F) + 2
+/// times, otherwise the next control + 1 bytes are copied as they are
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let control = data[i];
+        i += 1;
+        if control & 0x80 != 0 {
+            let Some(byte) = data.get(i) else { break };
+            out.extend(std::iter::repeat_n(*byte, (control & 0x7F) as usize + 2));
+            i += 1;
+        } else {
+            let end = (i + control as usize + 1).min(data.len());
+            out.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+    out
+}
+
+impl Cable for Printer {
+    /// the printer never clocks anything itself, it answers straight away
+    fn sync(&mut self, message: Message) -> Result<Message, String> {
+        Ok(Message { start: None, reply: message.start.map(|byte| self.receive(byte)) })
+    }
+}
+
+impl Drop for Printer {
+    /// a picture still on the paper is written out too
+    fn drop(&mut self) {
+        self.finish_page();
+    }
+}
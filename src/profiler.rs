@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use crate::callstack::CallStack;
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+use crate::symbols::Symbols;
+
+// how many of the hottest addresses and functions make it into the report
+const TOP: usize = 50;
+
+/// counts what the cpu spends its time on, for finding emulator bugs and
+/// for optimising homebrew.
+///
+/// every opcode and (bank aware) address gets a hit count, and the cycles
+/// of each step are charged to the call stack it ran in. the call stack is
+/// followed with the same tracking the debugger's `bt` uses, so interrupts
+/// show up as calls too. everything is written out once it is dropped:
+/// a readable report, and/or the cycles per stack in the folded format
+/// which flamegraph.pl and inferno read.
+pub struct Profiler {
+    opcodes: [u64; 256],
+    prefixed: [u64; 256],
+    addresses: HashMap<(u8, u16), u64>,
+    // cycles spent with exactly this stack, outermost function first
+    stacks: HashMap<Vec<(u8, u16)>, u64>,
+    calls: CallStack,
+    // the functions in `calls`, kept so the stacks map can be looked up without allocating
+    current: Vec<(u8, u16)>,
+    instructions: u64,
+    cycles: u64,
+    symbols: Symbols,
+    report_path: Option<String>,
+    folded_path: Option<String>,
+}
+
+impl Profiler {
+    pub fn new(report_path: Option<String>, folded_path: Option<String>, symbols: Symbols) -> Self {
+        Self {
+            opcodes: [0; 256],
+            prefixed: [0; 256],
+            addresses: HashMap::new(),
+            stacks: HashMap::new(),
+            calls: CallStack::default(),
+            current: Vec::new(),
+            instructions: 0,
+            cycles: 0,
+            symbols,
+            report_path,
+            folded_path,
+        }
+    }
+
+    /// has to be called before every step
+    pub fn before_step(&mut self, cpu: &Cpu, memory: &Memory) {
+        self.calls.before_step(cpu, memory);
+        // interrupts being dispatched arent instructions
+        if cpu.halted() || cpu.interrupt_pending() {
+            return;
+        }
+        let pc = cpu.regs.pc;
+        match memory.peek(pc) {
+            0xCB => self.prefixed[memory.peek(pc.wrapping_add(1)) as usize] += 1,
+            opcode => self.opcodes[opcode as usize] += 1,
+        }
+        *self.addresses.entry((memory.rom_bank(pc), pc)).or_insert(0) += 1;
+        self.instructions += 1;
+    }
+
+    /// and this after it, with how many cycles the step took
+    pub fn after_step(&mut self, cpu: &Cpu, memory: &Memory, cycles: u8) {
+        // a call is paid for by the caller and a return by the function returning
+        self.cycles += cycles as u64;
+        match self.stacks.get_mut(self.current.as_slice()) {
+            Some(total) => *total += cycles as u64,
+            None => { self.stacks.insert(self.current.clone(), cycles as u64); }
+        }
+
+        let depth = self.calls.frames.len();
+        self.calls.after_step(cpu, memory);
+        if self.calls.frames.len() != depth {
+            self.current.clear();
+            self.current.extend(self.calls.frames.iter().map(|f| (f.bank, f.function)));
+        }
+    }
+
+    fn name(&self, (bank, address): (u8, u16)) -> String {
+        match self.symbols.describe(bank, address) {
+            None => format!("{bank:02X}:{address:04X}"),
+            Some(label) => label,
+        }
+    }
+
+    /// one line per stack, like `root;Main;DrawSprites 1234`
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<String> = self.stacks.iter()
+            .map(|(stack, cycles)| {
+                let mut line = String::from("root");
+                for function in stack {
+                    line.push(';');
+                    line.push_str(&self.name(*function));
+                }
+                format!("{line} {cycles}")
+            })
+            .collect();
+        stacks.sort();
+        let mut text = stacks.join("\n");
+        text.push('\n');
+        text
+    }
+
+    pub fn report(&self) -> String {
+        let mut text = String::new();
+        let percent = |n: u64, of: u64| if of == 0 { 0.0 } else { n as f64 * 100.0 / of as f64 };
+        writeln!(text, "{} instructions, {} cycles", self.instructions, self.cycles).unwrap();
+
+        // self cycles only count the function at the top of the stack, total
+        // cycles count every function on it, but recursion only once
+        let mut functions: HashMap<Option<(u8, u16)>, (u64, u64)> = HashMap::new();
+        for (stack, cycles) in &self.stacks {
+            functions.entry(stack.last().copied()).or_default().0 += cycles;
+            let mut seen = Vec::new();
+            for function in std::iter::once(None).chain(stack.iter().map(|f| Some(*f))) {
+                if !seen.contains(&function) {
+                    functions.entry(function).or_default().1 += cycles;
+                    seen.push(function);
+                }
+            }
+        }
+        let mut functions: Vec<_> = functions.into_iter().collect();
+        functions.sort_by_key(|(_, (own, total))| std::cmp::Reverse((*total, *own)));
+        writeln!(text, "\nfunctions by total cycles:").unwrap();
+        writeln!(text, "{:>12} {:>7} {:>12} {:>7}  function", "total", "%", "self", "%").unwrap();
+        for (function, (own, total)) in functions.iter().take(TOP) {
+            let name = match function {
+                None => "root".to_string(),
+                Some(f) => self.name(*f),
+            };
+            writeln!(
+                text, "{total:>12} {:>6.2}% {own:>12} {:>6.2}%  {name}",
+                percent(*total, self.cycles), percent(*own, self.cycles),
+            ).unwrap();
+        }
+
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by_key(|(location, hits)| (std::cmp::Reverse(**hits), **location));
+        writeln!(text, "\nhottest addresses:").unwrap();
+        for ((bank, address), hits) in addresses.iter().take(TOP) {
+            let label = match self.symbols.describe(*bank, *address) {
+                None => String::new(),
+                Some(l) => format!("  {l}"),
+            };
+            writeln!(text, "{hits:>12} {:>6.2}%  {bank:02X}:{address:04X}{label}", percent(**hits, self.instructions)).unwrap();
+        }
+
+        // opcodes that never ran are listed too, it shows what a test rom hasnt covered
+        for (title, counts, prefix) in [("opcodes", &self.opcodes, ""), ("cb prefixed opcodes", &self.prefixed, "CB ")] {
+            writeln!(text, "\n{title}:").unwrap();
+            for (opcode, count) in counts.iter().enumerate() {
+                writeln!(text, "{prefix}{opcode:02X} {count:>12}").unwrap();
+            }
+        }
+        text
+    }
+
+    fn write(&self) {
+        let outputs = [(&self.report_path, Self::report as fn(&Self) -> String), (&self.folded_path, Self::folded)];
+        for (path, contents) in outputs {
+            let Some(path) = path else { continue };
+            match std::fs::write(path, contents(self)) {
+                Err(e) => println!("could not write the profile to {path}: {e}"),
+                Ok(_) => println!("profile written to {path}"),
+            }
+        }
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        self.write();
+    }
+}
@@ -0,0 +1,72 @@
+use crate::memory::Memory;
+
+/// external ram, work ram and high ram, which is where games keep their variables
+pub const RAM: [(u16, u16); 2] = [(0xA000, 0xDFFF), (0xFF80, 0xFFFE)];
+
+/// how a value is compared, either against the last snapshot or a given value
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compare {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    GreaterEqual,
+    LessEqual,
+}
+
+impl Compare {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "=" | "==" | "unchanged" => Some(Self::Equal),
+            "!=" | "changed" => Some(Self::NotEqual),
+            ">" | "increased" => Some(Self::Greater),
+            "<" | "decreased" => Some(Self::Less),
+            ">=" => Some(Self::GreaterEqual),
+            "<=" => Some(Self::LessEqual),
+            _ => None,
+        }
+    }
+
+    pub fn test(self, value: u8, against: u8) -> bool {
+        match self {
+            Self::Equal => value == against,
+            Self::NotEqual => value != against,
+            Self::Greater => value > against,
+            Self::Less => value < against,
+            Self::GreaterEqual => value >= against,
+            Self::LessEqual => value <= against,
+        }
+    }
+}
+
+/// narrows memory down to the addresses holding a game variable.
+///
+/// it starts with every address in the snapshot and each filter throws
+/// away the ones that dont match, e.g. running a frame after losing a
+/// life and keeping whatever decreased. the values are snapshotted again
+/// after every filter so the next one compares against them
+pub struct RamSearch {
+    // the addresses still left and their value at the last snapshot
+    pub candidates: Vec<(u16, u8)>,
+}
+
+impl RamSearch {
+    pub fn new(memory: &Memory, ranges: &[(u16, u16)]) -> Self {
+        let candidates = ranges.iter()
+            .flat_map(|(start, end)| *start..=*end)
+            .map(|address| (address, memory.peek(address)))
+            .collect();
+        Self { candidates }
+    }
+
+    /// keeps the addresses whose current value compares true against
+    /// `value`, or against their last snapshot when there isnt one
+    pub fn filter(&mut self, memory: &Memory, compare: Compare, value: Option<u8>) {
+        self.candidates.retain_mut(|(address, last)| {
+            let now = memory.peek(*address);
+            let keep = compare.test(now, value.unwrap_or(*last));
+            *last = now;
+            keep
+        });
+    }
+}
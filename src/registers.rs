@@ -1,4 +1,5 @@
 use crate::{split_u16, combine_u8s};
+use crate::savestate::{SaveState, StateWriter, StateReader};
 
 ///  Flag Register
 ///   ________________________________
@@ -79,6 +80,35 @@ impl CpuRegisters {
             pc: 0,
         }
     }
+    /// the values the dmg boot rom leaves behind when it hands over to
+    /// the cartridge at 0x100. the boot rom isnt emulated so we start here
+    pub fn post_boot() -> Self {
+        let mut regs = Self::new();
+        regs.set_af(0x01B0);
+        regs.set_bc(0x0013);
+        regs.set_de(0x00D8);
+        regs.set_hl(0x014D);
+        regs.pc = 0x0100;
+        regs
+    }
+    /// the cgb boot rom leaves 0x11 in A, which is how games know they are on one
+    pub fn post_boot_cgb() -> Self {
+        let mut regs = Self::post_boot();
+        regs.set_af(0x1180);
+        regs.set_bc(0x0000);
+        regs.set_de(0xFF56);
+        regs.set_hl(0x000D);
+        regs
+    }
+    /// the sgb boot rom leaves A the same as the dmg, only the others differ
+    pub fn post_boot_sgb() -> Self {
+        let mut regs = Self::post_boot();
+        regs.set_af(0x0100);
+        regs.set_bc(0x0014);
+        regs.set_de(0x0000);
+        regs.set_hl(0xC060);
+        regs
+    }
     // 16 bit register collectors
     pub fn set_bc(&mut self, data: u16) { (self.b, self.c) = split_u16(data) }
     pub fn set_de(&mut self, data: u16) { (self.d, self.e) = split_u16(data) }
@@ -117,4 +147,27 @@ impl CpuRegisters {
     pub fn jump_pc(&mut self, val: i8) {
         self.pc = self.pc.wrapping_add_signed(val as i16);
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for CpuRegisters {
+    fn save_state(&self, writer: &mut StateWriter) {
+        for reg in [self.a, self.f.as_u8(), self.b, self.c, self.d, self.e, self.h, self.l] {
+            writer.u8(reg);
+        }
+        writer.u16(self.sp);
+        writer.u16(self.pc);
+    }
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        self.a = reader.u8()?;
+        self.f = Flags::from_u8(reader.u8()?);
+        self.b = reader.u8()?;
+        self.c = reader.u8()?;
+        self.d = reader.u8()?;
+        self.e = reader.u8()?;
+        self.h = reader.u8()?;
+        self.l = reader.u8()?;
+        self.sp = reader.u16()?;
+        self.pc = reader.u16()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::VecDeque;
+
+use crate::gameboy::GameBoy;
+
+/// a ring buffer of save states which can be stepped backwards through.
+///
+/// only the newest state is kept whole. every older one is stored as the
+/// difference to the state after it (xor'd, then the runs of zeros squashed)
+/// so that most of the 64KB memory costs nothing when it hasnt changed.
+/// going back a step undoes the newest difference, and the oldest differences
+/// are simply dropped once the buffer grows past its budget.
+pub struct Rewind {
+    deltas: VecDeque<Delta>,
+    latest: Option<Vec<u8>>,
+    // how many frames between each snapshot
+    interval: u32,
+    frames: u32,
+    max_bytes: usize,
+    used_bytes: usize,
+}
+
+struct Delta {
+    // length of the older state, states dont have to all be the same size
+    len: usize,
+    data: Vec<u8>,
+}
+
+impl Rewind {
+    pub fn new(max_bytes: usize, interval: u32) -> Self {
+        Self {
+            deltas: VecDeque::new(),
+            latest: None,
+            interval: interval.max(1),
+            frames: 0,
+            max_bytes,
+            used_bytes: 0,
+        }
+    }
+
+    /// should be called after every frame, a snapshot is taken every `interval` of them
+    pub fn frame_finished(&mut self, gameboy: &GameBoy) {
+        self.frames += 1;
+        if self.frames < self.interval {
+            return;
+        }
+        self.frames = 0;
+        self.push(gameboy.save_state());
+    }
+
+    fn push(&mut self, state: Vec<u8>) {
+        if let Some(older) = self.latest.take() {
+            let delta = Delta { len: older.len(), data: compress(&xor(&older, &state)) };
+            self.used_bytes += delta.data.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state);
+
+        while self.used_bytes > self.max_bytes {
+            match self.deltas.pop_front() {
+                None => break,
+                Some(d) => self.used_bytes -= d.data.len(),
+            }
+        }
+    }
+
+    /// forgets every snapshot, rewinding starts over from the next one
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.latest = None;
+        self.frames = 0;
+        self.used_bytes = 0;
+    }
+
+    /// returns the state from one snapshot ago, or none once the buffer runs out
+    pub fn step_back(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        self.used_bytes -= delta.data.len();
+        let newer = self.latest.as_ref()?;
+        let mut older = xor(newer, &decompress(&delta.data));
+        older.resize(delta.len, 0);
+        self.latest = Some(older.clone());
+        self.frames = 0;
+        Some(older)
+    }
+}
+
+/// the shorter of the two is treated as if it was padded with zeros
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let len = a.len().max(b.len());
+    (0..len).map(|i| a.get(i).unwrap_or(&0) ^ b.get(i).unwrap_or(&0)).collect()
+}
+
+/// the data is split into (zeros, literals) pairs, each stored as
+/// | zero count | literal count | literal bytes... |
+/// with the counts written as varints
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let zeros = data[i..].iter().take_while(|b| **b == 0).count();
+        i += zeros;
+        let literals = data[i..].iter().take_while(|b| **b != 0).count();
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend(&data[i..i+literals]);
+        i += literals;
+    }
+    out
+}
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let zeros = read_varint(data, &mut i);
+        let literals = read_varint(data, &mut i);
+        out.resize(out.len() + zeros, 0);
+        out.extend(&data[i..i+literals]);
+        i += literals;
+    }
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+fn read_varint(data: &[u8], i: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*i];
+        *i += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// states that change in a few places each time, grow and shrink, and have long runs of zeros
+    fn states() -> Vec<Vec<u8>> {
+        let mut state: Vec<u8> = (0..5000).map(|i| if i % 300 < 200 { 0 } else { i as u8 }).collect();
+        let mut states = vec![state.clone()];
+        for n in 1..12u8 {
+            state[n as usize * 37] ^= n;
+            state[4000 + n as usize] = 0;
+            state[100..300].fill(n);
+            match n % 3 {
+                0 => state.extend(vec![n; 200]),
+                1 => state.truncate(state.len() - 150),
+                _ => (),
+            }
+            states.push(state.clone());
+        }
+        states
+    }
+
+    #[test]
+    fn steps_back_through_every_state() {
+        let states = states();
+        let mut rewind = Rewind::new(1 << 20, 1);
+        for state in &states {
+            rewind.push(state.clone());
+        }
+        for state in states.iter().rev().skip(1) {
+            assert!(rewind.step_back().as_ref() == Some(state));
+        }
+        assert_eq!(rewind.step_back(), None);
+        assert_eq!(rewind.used_bytes, 0);
+    }
+
+    #[test]
+    fn rewinds_a_gameboy() {
+        let mut gameboy = GameBoy::new(vec![0; 0x8000]);
+        let mut rewind = Rewind::new(1 << 20, 2);
+        let mut saved = Vec::new();
+        for frame in 1..=10 {
+            gameboy.run_frame();
+            rewind.frame_finished(&gameboy);
+            if frame % 2 == 0 {
+                saved.push(gameboy.save_state());
+            }
+        }
+        for state in saved.iter().rev().skip(1) {
+            let older = rewind.step_back().unwrap();
+            assert!(&older == state);
+            gameboy.load_state(&older).unwrap();
+            assert!(&gameboy.save_state() == state);
+        }
+        assert_eq!(rewind.step_back(), None);
+    }
+
+    #[test]
+    fn oldest_states_are_dropped_past_the_budget() {
+        let states = states();
+        let mut rewind = Rewind::new(600, 1);
+        for state in &states {
+            rewind.push(state.clone());
+        }
+        assert!(rewind.used_bytes <= 600);
+        let kept = rewind.deltas.len();
+        assert!(kept > 0 && kept < states.len() - 1);
+        for state in states.iter().rev().skip(1).take(kept) {
+            assert!(rewind.step_back().as_ref() == Some(state));
+        }
+        assert_eq!(rewind.step_back(), None);
+    }
+
+    #[test]
+    fn compression_round_trip() {
+        for data in [vec![], vec![0; 1000], vec![5; 1000], (0..=255).cycle().take(3000).collect(), [vec![0; 200], vec![1], vec![0; 70000], vec![9, 9]].concat()] {
+            assert_eq!(decompress(&compress(&data)), data);
+        }
+        assert!(compress(&vec![0; 70000]).len() < 8);
+    }
+}
@@ -0,0 +1,106 @@
+/// the binary format used for save states.
+///
+///  ___________________________________________
+/// | "GBSS" | version: u16 | title: 16 bytes | sections...
+///  -------------------------------------------
+///
+/// everything is little endian. each part of the machine writes its own
+/// section in a fixed order, the reader knows which version it is reading
+/// so sections can fill in defaults for fields older versions didnt have.
+pub const MAGIC: &[u8; 4] = b"GBSS";
+/// bump this whenever a section changes, and handle the old layout in the
+/// section's `load_state` by checking `reader.version`
+pub const VERSION: u16 = 7;
+
+pub trait SaveState {
+    fn save_state(&self, writer: &mut StateWriter);
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String>;
+}
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+impl StateWriter {
+    pub fn new(title: &[u8]) -> Self {
+        let mut writer = Self { data: Vec::new() };
+        writer.data.extend(MAGIC);
+        writer.u16(VERSION);
+        writer.title(title);
+        writer
+    }
+    fn title(&mut self, title: &[u8]) {
+        let mut padded = [0; 16];
+        let len = title.len().min(16);
+        padded[..len].copy_from_slice(&title[..len]);
+        self.data.extend(padded);
+    }
+
+    pub fn u8(&mut self, v: u8) { self.data.push(v) }
+    pub fn bool(&mut self, v: bool) { self.data.push(v as u8) }
+    pub fn u16(&mut self, v: u16) { self.data.extend(v.to_le_bytes()) }
+    pub fn u32(&mut self, v: u32) { self.data.extend(v.to_le_bytes()) }
+    pub fn u64(&mut self, v: u64) { self.data.extend(v.to_le_bytes()) }
+    /// the length is stored first so the reader knows how much to take
+    pub fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.data.extend(v);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // the version the state was saved with
+    pub version: u16,
+}
+impl<'a> StateReader<'a> {
+    /// checks the header, the state has to belong to the game with this title
+    pub fn new(data: &'a [u8], title: &[u8]) -> Result<Self, String> {
+        if data.len() < 4 || &data[0..4] != MAGIC {
+            return Err("not a save state".to_string());
+        }
+        let mut reader = Self { data, pos: 4, version: 0 };
+        reader.version = reader.u16()?;
+        if reader.version > VERSION {
+            return Err(format!("save state version {} is newer than this emulator", reader.version));
+        }
+
+        let mut expected = StateWriter { data: Vec::new() };
+        expected.title(title);
+        if reader.take(16)? != expected.data.as_slice() {
+            return Err("save state belongs to a different game".to_string());
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        match self.data.get(self.pos..self.pos+len) {
+            None => Err("save state ended early".to_string()),
+            Some(s) => {
+                self.pos += len;
+                Ok(s)
+            }
+        }
+    }
+    pub fn u8(&mut self) -> Result<u8, String> { Ok(self.take(1)?[0]) }
+    pub fn bool(&mut self) -> Result<bool, String> { Ok(self.u8()? != 0) }
+    pub fn u16(&mut self) -> Result<u16, String> { Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap())) }
+    pub fn u32(&mut self) -> Result<u32, String> { Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    pub fn u64(&mut self) -> Result<u64, String> { Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())) }
+    pub fn bytes(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    /// makes sure nothing was left over, which would mean the sections are out of sync
+    pub fn finish(self) -> Result<(), String> {
+        if self.pos != self.data.len() {
+            return Err("save state has trailing data".to_string());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::savestate::{StateWriter, StateReader};
+
+pub const SB: u16 = 0xFF01;
+pub const SC: u16 = 0xFF02;
+
+/// what one end of the link cable tells the other each time they sync up, see `link`
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Message {
+    // a byte this end started sending with its own clock
+    pub start: Option<u8>,
+    // the byte shifted back for the other end's start
+    pub reply: Option<u8>,
+}
+
+impl Message {
+    pub fn to_bytes(self) -> [u8; 3] {
+        let flags = self.start.is_some() as u8 | (self.reply.is_some() as u8) << 1;
+        [flags, self.start.unwrap_or(0), self.reply.unwrap_or(0)]
+    }
+    pub fn from_bytes(bytes: [u8; 3]) -> Self {
+        Self {
+            start: (bytes[0] & 1 != 0).then_some(bytes[1]),
+            reply: (bytes[0] & 2 != 0).then_some(bytes[2]),
+        }
+    }
+}
+
+/// the serial port's transfer in progress. SB and SC themselves are normal io registers.
+///
+/// a transfer with the internal clock shifts 8 bits out at 8192Hz (or 262144Hz
+/// with the cgb's fast clock), and gets back whatever the other end had in SB.
+/// with the external clock the other end does the shifting, so without a
+/// cable it never finishes
+#[derive(Default)]
+pub struct Serial {
+    // cpu cycles until our clock has shifted all 8 bits
+    shifting: u32,
+    // our transfer hasnt heard back from the other end yet
+    waiting: bool,
+    reply: Option<u8>,
+    // without a cable everything shifted in is 1s
+    pub connected: bool,
+    outgoing: Message,
+}
+
+impl Serial {
+    /// writing SC with bits 7 and 0 set starts a transfer on our clock
+    pub fn start(&mut self, sb: u8, sc: u8, is_cgb: bool) {
+        if sc & 0x81 != 0x81 {
+            return;
+        }
+        let per_bit = if is_cgb && sc & 0b10 != 0 { 16 } else { 512 };
+        self.shifting = 8 * per_bit;
+        self.reply = None;
+        self.waiting = self.connected;
+        if self.connected {
+            self.outgoing.start = Some(sb);
+        }
+    }
+
+    /// moves our clock on, returns the byte shifted in once the transfer is done
+    pub fn tick(&mut self, cycles: u32) -> Option<u8> {
+        if self.shifting == 0 {
+            return None;
+        }
+        self.shifting = self.shifting.saturating_sub(cycles);
+        self.finished()
+    }
+
+    /// a transfer is only done once it has been clocked out and the other end has answered
+    fn finished(&mut self) -> Option<u8> {
+        if self.shifting != 0 || self.waiting {
+            return None;
+        }
+        Some(self.reply.take().unwrap_or(0xFF))
+    }
+
+    /// the other end started a transfer, returns the byte to put in SB if
+    /// ours was waiting on its clock. an idle port still answers, with 1s
+    pub fn receive_start(&mut self, byte: u8, sb: u8, sc: u8) -> Option<u8> {
+        let ready = sc & 0x81 == 0x80;
+        self.outgoing.reply = Some(if ready { sb } else { 0xFF });
+        ready.then_some(byte)
+    }
+
+    /// the answer to our transfer, returns the byte shifted in if our clock has already finished
+    pub fn receive_reply(&mut self, byte: u8) -> Option<u8> {
+        if !self.waiting {
+            return None;
+        }
+        self.waiting = false;
+        self.reply = Some(byte);
+        if self.shifting == 0 {
+            return self.finished();
+        }
+        None
+    }
+
+    pub fn take_outgoing(&mut self) -> Message {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.u32(self.shifting);
+        writer.bool(self.waiting);
+        writer.bool(self.reply.is_some());
+        writer.u8(self.reply.unwrap_or(0));
+    }
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        *self = Self { connected: self.connected, ..Self::default() };
+        // version 5 was from before the serial port
+        if reader.version < 6 {
+            return Ok(());
+        }
+        self.shifting = reader.u32()?;
+        self.waiting = reader.bool()? && self.connected;
+        let replied = reader.bool()?;
+        let reply = reader.u8()?;
+        self.reply = replied.then_some(reply);
+        Ok(())
+    }
+}
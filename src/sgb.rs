@@ -0,0 +1,449 @@
+use crate::savestate::{StateWriter, StateReader};
+use crate::gameboy::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+/// the whole snes picture, the game boy's screen sits in the middle of the border
+pub const SGB_WIDTH: u32 = 256;
+pub const SGB_HEIGHT: u32 = 224;
+const SCREEN_LEFT: usize = 48;
+const SCREEN_TOP: usize = 40;
+
+// the commands, the top 5 bits of a command's first byte
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const MLT_REQ: u8 = 0x11;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const MASK_EN: u8 = 0x17;
+
+/// header byte 0x0146 is 0x03 for sgb support, which only counts with the new licensee code
+pub fn is_sgb_rom(rom: &[u8]) -> bool {
+    rom.get(0x146) == Some(&0x03) && rom.get(0x14B) == Some(&0x33)
+}
+
+/// what MASK_EN does to the game boy's screen, the border is always drawn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mask {
+    None,
+    // keeps showing the last frame
+    Freeze,
+    Black,
+    // everything is colour 0
+    Blank,
+}
+
+/// a CHR_TRN or PCT_TRN waiting for the next frame to be shown,
+/// which is when the data is taken from the screen
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Transfer {
+    // tiles 0x00-0x7F or 0x80-0xFF
+    Tiles(u8),
+    Border,
+}
+
+/// the super game boy. games send it commands a bit at a time through P1,
+/// and it colours the screen through 4 palettes picked per 8x8 cell and
+/// draws a border around it.
+///
+/// only the commands for palettes, attributes, masking, the border and
+/// multiplayer detection are understood, the rest are ignored
+pub struct Sgb {
+    // the packet coming in and how many bits of it have arrived
+    packet: [u8; 16],
+    bits: usize,
+    receiving: bool,
+    // a bit is only taken once P1 has gone back to 0x30 since the last one
+    ready: bool,
+    last_p1: u8,
+    // the packets of a command longer than one packet
+    command: Vec<u8>,
+    // colour 0 is shared between all four
+    pub palettes: [[u16; 4]; 4],
+    // which palette each 8x8 cell of the screen uses, 20 cells a row
+    pub attributes: [u8; 20 * 18],
+    pub mask: Mask,
+    transfer: Option<Transfer>,
+    // 256 snes tiles of 32 bytes each
+    border_tiles: Vec<u8>,
+    // 32x32 entries, only the top 28 rows are shown
+    border_map: Vec<u16>,
+    // palettes 4-7, which the border uses
+    border_palettes: [[u16; 16]; 4],
+    // multiplayer, which is how games find out they are on an sgb
+    pub players: u8,
+    player: u8,
+    frozen: Vec<u16>,
+}
+
+impl Default for Sgb {
+    fn default() -> Self {
+        Self {
+            packet: [0; 16],
+            bits: 0,
+            receiving: false,
+            ready: false,
+            last_p1: 0x30,
+            command: Vec::new(),
+            palettes: [[0x7FFF, 0x5294, 0x294A, 0x0000]; 4],
+            attributes: [0; 20 * 18],
+            mask: Mask::None,
+            transfer: None,
+            border_tiles: vec![0; 256 * 32],
+            border_map: vec![0; 32 * 32],
+            border_palettes: [[0; 16]; 4],
+            players: 1,
+            player: 0,
+            frozen: vec![0; (SCREEN_WIDTH * SCREEN_HEIGHT) as usize],
+        }
+    }
+}
+
+impl Sgb {
+    /// P14 and P15 low together resets, then each bit is P14 low for a 0 or
+    /// P15 low for a 1 with both high in between. 128 bits make a packet,
+    /// followed by a 0 to stop it
+    pub fn write_p1(&mut self, p1: u8) {
+        let select = p1 & 0x30;
+        // the next player is picked as P15 goes high again
+        if !self.receiving && select == 0x30 && self.last_p1 & 0x20 == 0 && self.players > 1 {
+            self.player = (self.player + 1) % self.players;
+        }
+        self.last_p1 = select;
+        match select {
+            0x00 => {
+                self.receiving = true;
+                self.ready = false;
+                self.bits = 0;
+                self.packet = [0; 16];
+            }
+            0x30 => self.ready = true,
+            _ if !self.receiving || !self.ready => {}
+            _ => {
+                self.ready = false;
+                let bit = select == 0x10;
+                if self.bits == 128 {
+                    self.receiving = false;
+                    // a 1 where the stop bit should be throws the packet away
+                    if !bit {
+                        self.packet_received();
+                    }
+                    return;
+                }
+                if bit {
+                    self.packet[self.bits / 8] |= 1 << (self.bits % 8);
+                }
+                self.bits += 1;
+            }
+        }
+    }
+
+    /// while more than one player is plugged in, P1 with nothing selected
+    /// gives which one is being read, 0xF for the first, 0xE for the second...
+    pub fn read_p1(&self, select: u8, buttons_p1: u8) -> u8 {
+        if self.players > 1 && select & 0x30 == 0x30 {
+            return 0xF0 | (0x0F - self.player);
+        }
+        match self.player {
+            0 => buttons_p1,
+            // nobody is pressing anything on the other controllers
+            _ => 0xC0 | (select & 0x30) | 0x0F,
+        }
+    }
+
+    fn packet_received(&mut self) {
+        self.command.extend_from_slice(&self.packet);
+        let length = (self.command[0] & 0b111).max(1) as usize;
+        if self.command.len() >= length * 16 {
+            let command = std::mem::take(&mut self.command);
+            self.run(&command);
+        }
+    }
+
+    fn run(&mut self, data: &[u8]) {
+        match data[0] >> 3 {
+            PAL01 => self.set_palettes(data, 0, 1),
+            PAL23 => self.set_palettes(data, 2, 3),
+            PAL03 => self.set_palettes(data, 0, 3),
+            PAL12 => self.set_palettes(data, 1, 2),
+            ATTR_BLK => self.attr_blk(data),
+            ATTR_LIN => self.attr_lin(data),
+            ATTR_DIV => self.attr_div(data),
+            ATTR_CHR => self.attr_chr(data),
+            MLT_REQ => {
+                self.players = match data[1] & 0b11 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                self.player = 0;
+            }
+            CHR_TRN => self.transfer = Some(Transfer::Tiles(data[1] & 1)),
+            PCT_TRN => self.transfer = Some(Transfer::Border),
+            MASK_EN => {
+                self.mask = match data[1] & 0b11 {
+                    0 => Mask::None,
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    _ => Mask::Blank,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// colour 0 for every palette, then colours 1-3 of the two given
+    fn set_palettes(&mut self, data: &[u8], first: usize, second: usize) {
+        let colour = |i: usize| u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]) & 0x7FFF;
+        for palette in &mut self.palettes {
+            palette[0] = colour(0);
+        }
+        for i in 1..4 {
+            self.palettes[first][i] = colour(i);
+            self.palettes[second][i] = colour(i + 3);
+        }
+    }
+
+    /// rectangles with a palette for the cells inside, on the edge and outside of each
+    fn attr_blk(&mut self, data: &[u8]) {
+        let sets = (data[1] as usize).min(18);
+        for set in data[2..].chunks_exact(6).take(sets) {
+            let (control, palettes) = (set[0], set[1]);
+            let (x1, y1, x2, y2) = (set[2] as usize, set[3] as usize, set[4] as usize, set[5] as usize);
+            let inside = control & 1 != 0;
+            let outside = control & 4 != 0;
+            // with only the inside or outside given, the edge goes along with it
+            let edge = match control & 0b111 {
+                0b001 => Some(palettes & 0b11),
+                0b100 => Some((palettes >> 4) & 0b11),
+                c if c & 0b010 != 0 => Some((palettes >> 2) & 0b11),
+                _ => None,
+            };
+            for y in 0..18 {
+                for x in 0..20 {
+                    let within = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                    let on_edge = within && (x == x1 || x == x2 || y == y1 || y == y2);
+                    let palette = match (within, on_edge) {
+                        (true, true) => edge,
+                        (true, false) if inside => Some(palettes & 0b11),
+                        (false, _) if outside => Some((palettes >> 4) & 0b11),
+                        _ => None,
+                    };
+                    if let Some(palette) = palette {
+                        self.attributes[y * 20 + x] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    /// whole rows or columns
+    fn attr_lin(&mut self, data: &[u8]) {
+        let lines = data[1] as usize;
+        for line in data[2..].iter().take(lines) {
+            let (index, palette) = ((line & 0x1F) as usize, (line >> 5) & 0b11);
+            if line & 0x80 != 0 {
+                if index < 18 {
+                    self.attributes[index * 20..index * 20 + 20].fill(palette);
+                }
+            } else if index < 20 {
+                for y in 0..18 {
+                    self.attributes[y * 20 + index] = palette;
+                }
+            }
+        }
+    }
+
+    /// splits the screen into two at a row or column, which gets a palette of its own
+    fn attr_div(&mut self, data: &[u8]) {
+        let (control, at) = (data[1], data[2] as usize);
+        let (after, before, on) = (control & 0b11, (control >> 2) & 0b11, (control >> 4) & 0b11);
+        let horizontal = control & 0x40 != 0;
+        for y in 0..18 {
+            for x in 0..20 {
+                let position = if horizontal { y } else { x };
+                self.attributes[y * 20 + x] = match position.cmp(&at) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on,
+                    std::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    /// a palette for each cell in turn from a starting one, four to a byte
+    fn attr_chr(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[1] as usize, data[2] as usize);
+        let count = (u16::from_le_bytes([data[3], data[4]]) as usize).min(20 * 18);
+        let down = data[5] & 1 != 0;
+        for i in 0..count {
+            let Some(byte) = data.get(6 + i / 4) else { break };
+            if x >= 20 || y >= 18 {
+                break;
+            }
+            self.attributes[y * 20 + x] = (byte >> (6 - (i % 4) * 2)) & 0b11;
+            if down {
+                y += 1;
+                if y == 18 {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == 20 {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    pub fn transfer_pending(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    /// finishes a CHR_TRN or PCT_TRN with the 4KB of tiles on screen, see `Memory::screen_tile_data`
+    pub fn transfer(&mut self, data: &[u8]) {
+        match self.transfer.take() {
+            Some(Transfer::Tiles(half)) => {
+                let start = half as usize * 128 * 32;
+                self.border_tiles[start..start + 4096].copy_from_slice(&data[..4096]);
+            }
+            Some(Transfer::Border) => {
+                for (i, entry) in data[..0x800].chunks_exact(2).enumerate() {
+                    self.border_map[i] = u16::from_le_bytes([entry[0], entry[1]]);
+                }
+                for (i, colour) in data[0x800..0x880].chunks_exact(2).enumerate() {
+                    self.border_palettes[i / 16][i % 16] = u16::from_le_bytes([colour[0], colour[1]]) & 0x7FFF;
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// a pixel of the border as a colour number 0-15, 0 lets what is behind show through
+    fn border_pixel(&self, x: usize, y: usize) -> (u8, usize) {
+        let entry = self.border_map[(y / 8) * 32 + x / 8];
+        let tile = &self.border_tiles[(entry & 0xFF) as usize * 32..][..32];
+        let (mut tx, mut ty) = (x % 8, y % 8);
+        if entry & 0x4000 != 0 {
+            tx = 7 - tx;
+        }
+        if entry & 0x8000 != 0 {
+            ty = 7 - ty;
+        }
+        // snes tiles keep two bitplanes in each half
+        let bit = |byte: u8| (byte >> (7 - tx)) & 1;
+        let colour = bit(tile[ty * 2]) | bit(tile[ty * 2 + 1]) << 1
+            | bit(tile[16 + ty * 2]) << 2 | bit(tile[16 + ty * 2 + 1]) << 3;
+        // palettes 4-7 are the border's
+        let palette = ((entry >> 10) & 0b111) as usize;
+        (colour, palette.saturating_sub(4))
+    }
+
+    /// colours the frame of dmg shades and puts it inside the border, giving
+    /// `SGB_WIDTH`x`SGB_HEIGHT` cgb style colours, see `pallete_to_rgba`
+    pub fn render(&mut self, frame: &[u16]) -> Vec<u16> {
+        if self.mask != Mask::Freeze {
+            self.frozen.clear();
+            self.frozen.extend_from_slice(frame);
+        }
+        let background = 0x8000 | self.palettes[0][0];
+        let mut out = vec![background; (SGB_WIDTH * SGB_HEIGHT) as usize];
+        for (i, shade) in self.frozen.iter().enumerate() {
+            let (x, y) = (i % SCREEN_WIDTH as usize, i / SCREEN_WIDTH as usize);
+            let colour = match self.mask {
+                Mask::Black => 0,
+                Mask::Blank => self.palettes[0][0],
+                _ => {
+                    let palette = self.attributes[(y / 8) * 20 + x / 8] as usize;
+                    self.palettes[palette][(*shade & 0b11) as usize]
+                }
+            };
+            out[(y + SCREEN_TOP) * SGB_WIDTH as usize + x + SCREEN_LEFT] = 0x8000 | colour;
+        }
+        for y in 0..SGB_HEIGHT as usize {
+            for x in 0..SGB_WIDTH as usize {
+                let (colour, palette) = self.border_pixel(x, y);
+                if colour != 0 {
+                    out[y * SGB_WIDTH as usize + x] = 0x8000 | self.border_palettes[palette][colour as usize];
+                }
+            }
+        }
+        out
+    }
+
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        let words = |words: &mut dyn Iterator<Item = &u16>| -> Vec<u8> {
+            words.flat_map(|w| w.to_le_bytes()).collect()
+        };
+        writer.bytes(&self.packet);
+        writer.u8(self.bits as u8);
+        writer.bool(self.receiving);
+        writer.bool(self.ready);
+        writer.u8(self.last_p1);
+        writer.bytes(&self.command);
+        writer.bytes(&words(&mut self.palettes.iter().flatten()));
+        writer.bytes(&self.attributes);
+        writer.u8(self.mask as u8);
+        writer.u8(match self.transfer {
+            None => 0,
+            Some(Transfer::Tiles(half)) => 1 + half,
+            Some(Transfer::Border) => 3,
+        });
+        writer.bytes(&self.border_tiles);
+        writer.bytes(&words(&mut self.border_map.iter()));
+        writer.bytes(&words(&mut self.border_palettes.iter().flatten()));
+        writer.u8(self.players);
+        writer.u8(self.player);
+        writer.bytes(&words(&mut self.frozen.iter()));
+    }
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), String> {
+        let wrong_size = || "save state sgb data is the wrong size".to_string();
+        let mut words = |reader: &mut StateReader, into: &mut [u16]| -> Result<(), String> {
+            let data = reader.bytes()?;
+            if data.len() != into.len() * 2 {
+                return Err(wrong_size());
+            }
+            for (word, bytes) in into.iter_mut().zip(data.chunks_exact(2)) {
+                *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+            }
+            Ok(())
+        };
+        self.packet = reader.bytes()?.try_into().map_err(|_| wrong_size())?;
+        self.bits = reader.u8()? as usize;
+        self.receiving = reader.bool()?;
+        self.ready = reader.bool()?;
+        self.last_p1 = reader.u8()?;
+        self.command = reader.bytes()?.to_vec();
+        words(reader, self.palettes.as_flattened_mut())?;
+        self.attributes = reader.bytes()?.try_into().map_err(|_| wrong_size())?;
+        self.mask = match reader.u8()? {
+            1 => Mask::Freeze,
+            2 => Mask::Black,
+            3 => Mask::Blank,
+            _ => Mask::None,
+        };
+        self.transfer = match reader.u8()? {
+            1 => Some(Transfer::Tiles(0)),
+            2 => Some(Transfer::Tiles(1)),
+            3 => Some(Transfer::Border),
+            _ => None,
+        };
+        let tiles = reader.bytes()?;
+        if tiles.len() != self.border_tiles.len() {
+            return Err(wrong_size());
+        }
+        self.border_tiles.copy_from_slice(tiles);
+        words(reader, &mut self.border_map)?;
+        words(reader, self.border_palettes.as_flattened_mut())?;
+        self.players = reader.u8()?;
+        self.player = reader.u8()?;
+        words(reader, &mut self.frozen)?;
+        Ok(())
+    }
+}
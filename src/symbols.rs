@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+/// labels loaded from an RGBDS `.sym` file, lines look like `01:4A2F MainLoop`
+/// and anything after a `;` is a comment.
+///
+/// addresses below 0x4000 are always bank 0 and the bank only matters for
+/// 0x4000-0x7FFF, since that is the only area which gets switched.
+#[derive(Default)]
+pub struct Symbols {
+    // sorted by bank then address, so the closest label before an address can be found
+    labels: Vec<(u8, u16, String)>,
+    by_name: HashMap<String, (u8, u16)>,
+}
+
+impl Symbols {
+    pub fn parse(text: &str) -> Self {
+        let mut symbols = Self::default();
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            let Some((location, name)) = line.split_once(char::is_whitespace) else { continue };
+            let Some((bank, address)) = location.split_once(':') else { continue };
+            let (Ok(bank), Ok(address)) = (u8::from_str_radix(bank, 16), u16::from_str_radix(address, 16)) else { continue };
+            let name = name.trim().to_string();
+            symbols.by_name.insert(name.clone(), (bank, address));
+            symbols.labels.push((bank, address, name));
+        }
+        symbols.labels.sort_by_key(|(bank, address, _)| (*bank, *address));
+        symbols
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Err(e) => Err(format!("could not read {path}: {e}")),
+            Ok(text) => Ok(Self::parse(&text)),
+        }
+    }
+
+    /// RGBDS puts the sym file next to the rom with the same name, so that is
+    /// tried when one isnt given. roms without one just get no labels
+    pub fn for_rom(rom_path: &str) -> Self {
+        let path = std::path::Path::new(rom_path).with_extension("sym");
+        match std::fs::read_to_string(path) {
+            Err(_) => Self::default(),
+            Ok(text) => Self::parse(&text),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// every label as bank, address and name, in address order
+    pub fn iter(&self) -> impl Iterator<Item = (u8, u16, &str)> {
+        self.labels.iter().map(|(bank, address, name)| (*bank, *address, name.as_str()))
+    }
+
+    /// only the switchable rom area cares which bank it is in
+    fn same_bank(label_bank: u8, bank: u8, address: u16) -> bool {
+        !(0x4000..0x8000).contains(&address) || label_bank == bank
+    }
+
+    /// the label exactly at the address
+    pub fn label_at(&self, bank: u8, address: u16) -> Option<&str> {
+        self.labels.iter()
+            .find(|(b, a, _)| *a == address && Self::same_bank(*b, bank, address))
+            .map(|(_, _, name)| name.as_str())
+    }
+
+    /// the address as `Label` or `Label+$12`, using the closest label before
+    /// it within the same 16KB area of memory
+    pub fn describe(&self, bank: u8, address: u16) -> Option<String> {
+        let (_, label_address, name) = self.labels.iter()
+            .filter(|(b, a, _)| *a <= address && *a & 0xC000 == address & 0xC000 && Self::same_bank(*b, bank, address))
+            .max_by_key(|(_, a, _)| *a)?;
+        let offset = address - label_address;
+        if offset == 0 {
+            Some(name.clone())
+        } else {
+            Some(format!("{name}+${offset:X}"))
+        }
+    }
+
+    /// looks up `Label` or `Label+<hex offset>`, giving back the bank and address
+    pub fn resolve(&self, text: &str) -> Option<(u8, u16)> {
+        let (name, offset) = match text.split_once('+') {
+            None => (text, 0),
+            Some((name, offset)) => (name, u16::from_str_radix(offset.trim_start_matches('$').trim_start_matches("0x"), 16).ok()?),
+        };
+        let (bank, address) = self.by_name.get(name)?;
+        Some((*bank, address.wrapping_add(offset)))
+    }
+}
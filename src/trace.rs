@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+use crate::symbols::Symbols;
+
+/// logs every instruction in the format Gameboy Doctor expects:
+///
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+///
+/// each line is the state just before the instruction at PC runs, so a
+/// trace from the post boot state can be diffed with other emulators' logs.
+/// the reference logs were made with LY stuck at 0x90, `--trace-doctor` does the same
+/// with symbols the location is added on the end, like `(MainLoop+$12)`
+pub struct Tracer {
+    out: BufWriter<File>,
+    // only instructions inside these get logged
+    pc_range: Option<(u16, u16)>,
+    bank: Option<u8>,
+    // stops logging once this many lines have been written
+    remaining: Option<u64>,
+    symbols: Option<Symbols>,
+}
+
+impl Tracer {
+    pub fn new(path: &str, pc_range: Option<(u16, u16)>, bank: Option<u8>, limit: Option<u64>, symbols: Option<Symbols>) -> Self {
+        let file = match File::create(path) {
+            Err(e) => panic!("could not create trace file {path}: {e}"),
+            Ok(f) => f,
+        };
+        Self { out: BufWriter::new(file), pc_range, bank, remaining: limit, symbols }
+    }
+
+    pub fn log(&mut self, cpu: &Cpu, memory: &Memory) {
+        if self.remaining == Some(0) {
+            return;
+        }
+        let regs = &cpu.regs;
+        if let Some((start, end)) = self.pc_range {
+            if regs.pc < start || regs.pc > end {
+                return;
+            }
+        }
+        if let Some(bank) = self.bank {
+            if memory.rom_bank(regs.pc) != bank {
+                return;
+            }
+        }
+
+        let mem = |i: u16| memory.peek(regs.pc.wrapping_add(i));
+        let (a, f) = crate::split_u16(regs.af());
+        let label = match &self.symbols {
+            None => None,
+            Some(symbols) => symbols.describe(memory.rom_bank(regs.pc), regs.pc),
+        };
+        let label = match label {
+            None => String::new(),
+            Some(l) => format!(" ({l})"),
+        };
+        let line = writeln!(
+            self.out,
+            "A:{a:02X} F:{f:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}{label}",
+            regs.b, regs.c, regs.d, regs.e, regs.h, regs.l, regs.sp, regs.pc,
+            mem(0), mem(1), mem(2), mem(3),
+        );
+        if line.is_err() {
+            // most likely out of disk space, no point carrying on
+            println!("could not write to the trace file, tracing stopped");
+            self.remaining = Some(0);
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt::Write as _;
+
+use crate::memory::Memory;
+use crate::png::{self, Image};
+use crate::pallete_to_rgba;
+
+const LCDC: u16 = 0xFF40;
+const SCY: u16 = 0xFF42;
+const SCX: u16 = 0xFF43;
+const BGP: u16 = 0xFF47;
+const OBP0: u16 = 0xFF48;
+const OBP1: u16 = 0xFF49;
+
+// the screen's outline on the background maps
+const VIEWPORT: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+// between the sprites on the oam sheet
+const GAP: [u8; 4] = [0x80, 0x80, 0xC0, 0xFF];
+
+/// which palette the tile sheet is drawn through
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Palette {
+    // the colour numbers as they are stored
+    Raw,
+    Bgp,
+    Obp0,
+    Obp1,
+}
+
+impl Palette {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "raw" => Some(Self::Raw),
+            "bgp" => Some(Self::Bgp),
+            "obp0" => Some(Self::Obp0),
+            "obp1" => Some(Self::Obp1),
+            _ => None,
+        }
+    }
+
+    fn value(self, memory: &Memory) -> u8 {
+        match self {
+            // maps every colour onto the shade with the same number
+            Self::Raw => 0b1110_0100,
+            Self::Bgp => memory.peek(BGP),
+            Self::Obp0 => memory.peek(OBP0),
+            Self::Obp1 => memory.peek(OBP1),
+        }
+    }
+}
+
+fn blank(width: u32, height: u32, colour: [u8; 4]) -> Image {
+    Image { width, height, rgba: colour.repeat((width * height) as usize) }
+}
+
+fn put(image: &mut Image, x: u32, y: u32, colour: [u8; 4]) {
+    let i = ((y * image.width + x) * 4) as usize;
+    image.rgba[i..i + 4].copy_from_slice(&colour);
+}
+
+fn shade(palette: u8, colour: u8) -> [u8; 4] {
+    let (r, g, b) = pallete_to_rgba(((palette >> (colour * 2)) & 0b11) as u16);
+    [r, g, b, 0xFF]
+}
+
+/// the colour number of a pixel, rows from `read_tile` keep the leftmost pixel in the top bits
+fn tile_pixel(tile: &[u16; 8], x: u32, y: u32) -> u8 {
+    ((tile[y as usize] >> ((7 - x) * 2)) & 0b11) as u8
+}
+
+fn draw_tile(image: &mut Image, tile: &[u16; 8], left: u32, top: u32, palette: u8) {
+    for y in 0..8 {
+        for x in 0..8 {
+            put(image, left + x, top + y, shade(palette, tile_pixel(tile, x, y)));
+        }
+    }
+}
+
+/// all 384 tiles in vram, 16 to a row, in the order they are stored
+pub fn tile_sheet(memory: &Memory, palette: Palette) -> Image {
+    let palette = palette.value(memory);
+    let mut image = blank(16 * 8, 24 * 8, [0; 4]);
+    for index in 0..384 {
+        let tile = memory.read_tile_at(0x8000 + index * 16);
+        draw_tile(&mut image, &tile, (index as u32 % 16) * 8, (index as u32 / 16) * 8, palette);
+    }
+    image
+}
+
+/// one of the two 32x32 tile maps through BGP, using whichever tile data
+/// LCDC currently selects. the map the background is using gets the
+/// SCX/SCY viewport drawn on it, wrapping around the edges like the screen does
+pub fn background_map(memory: &Memory, index: u8) -> Image {
+    let lcdc = memory.peek(LCDC);
+    let palette = memory.peek(BGP);
+    let map = memory.read_map(index);
+    let mut image = blank(256, 256, [0; 4]);
+    for (i, tile_index) in map.iter().enumerate() {
+        let address = if lcdc & 0b0001_0000 != 0 {
+            0x8000 + *tile_index as u16 * 16
+        } else {
+            // 0x8800 addressing, the index is signed around 0x9000
+            0x9000u16.wrapping_add((*tile_index as i8 as i16 * 16) as u16)
+        };
+        let tile = memory.read_tile_at(address);
+        draw_tile(&mut image, &tile, (i as u32 % 32) * 8, (i as u32 / 32) * 8, palette);
+    }
+
+    if (lcdc & 0b0000_1000) >> 3 == index {
+        let (scx, scy) = (memory.peek(SCX) as u32, memory.peek(SCY) as u32);
+        for x in 0..160 {
+            put(&mut image, (scx + x) % 256, scy, VIEWPORT);
+            put(&mut image, (scx + x) % 256, (scy + 143) % 256, VIEWPORT);
+        }
+        for y in 0..144 {
+            put(&mut image, scx, (scy + y) % 256, VIEWPORT);
+            put(&mut image, (scx + 159) % 256, (scy + y) % 256, VIEWPORT);
+        }
+    }
+    image
+}
+
+/// the 40 sprites, 8 to a row, drawn through their own palette and flips.
+/// colour 0 is left transparent, and 8x8 sprites leave the bottom of their cell empty
+pub fn oam_sheet(memory: &Memory) -> Image {
+    let tall = memory.peek(LCDC) & 0b0000_0100 != 0;
+    let mut image = blank(8 * 9 + 1, 5 * 17 + 1, GAP);
+    for index in 0..40 {
+        let [_, _, tile_index, flags] = memory.read_oam(index);
+        let palette = memory.peek(if flags & 0b0001_0000 != 0 { OBP1 } else { OBP0 });
+        let (left, top) = (1 + (index as u32 % 8) * 9, 1 + (index as u32 / 8) * 17);
+        let height = if tall { 16 } else { 8 };
+        for y in 0..16 {
+            for x in 0..8 {
+                if y >= height {
+                    put(&mut image, left + x, top + y, [0; 4]);
+                    continue;
+                }
+                let sy = if flags & 0b0100_0000 != 0 { height - 1 - y } else { y };
+                let sx = if flags & 0b0010_0000 != 0 { 7 - x } else { x };
+                // 8x16 sprites ignore the lowest bit of the tile index
+                let tile_index = if tall { (tile_index & 0xFE) + (sy / 8) as u8 } else { tile_index };
+                let colour = tile_pixel(&memory.read_tile(tile_index), sx, sy % 8);
+                let pixel = if colour == 0 { [0; 4] } else { shade(palette, colour) };
+                put(&mut image, left + x, top + y, pixel);
+            }
+        }
+    }
+    image
+}
+
+/// one row per sprite with its attributes decoded. the positions are
+/// given as stored, so they are 16 and 8 more than where they land on screen
+pub fn oam_table(memory: &Memory) -> String {
+    let mut text = String::from(" #    y    x  tile  prio  yflip  xflip  pal\n");
+    for index in 0..40 {
+        let [y, x, tile, flags] = memory.read_oam(index);
+        let flag = |bit: u8| if flags & bit != 0 { "yes" } else { "no" };
+        writeln!(
+            text, "{index:>2}  ${y:02X}  ${x:02X}   ${tile:02X}  {:<4}  {:<5}  {:<5}  OBP{}",
+            if flags & 0b1000_0000 != 0 { "bg" } else { "obj" },
+            flag(0b0100_0000), flag(0b0010_0000), (flags >> 4) & 1,
+        ).unwrap();
+    }
+    text
+}
+
+/// BGP, OBP0 and OBP1 as a row of four shades each, colour 0 on the left
+pub fn palette_sheet(memory: &Memory) -> Image {
+    let mut image = blank(4 * 16, 3 * 16, [0; 4]);
+    for (row, register) in [BGP, OBP0, OBP1].into_iter().enumerate() {
+        let palette = memory.peek(register);
+        for colour in 0..4 {
+            for y in 0..16 {
+                for x in 0..16 {
+                    put(&mut image, colour as u32 * 16 + x, row as u32 * 16 + y, shade(palette, colour));
+                }
+            }
+        }
+    }
+    image
+}
+
+/// each palette's value and the shade every colour number turns into,
+/// followed by the cgb's colour palettes as RGB555 when there are any
+pub fn palette_table(memory: &Memory) -> String {
+    let mut text = String::new();
+    for (name, register) in [("BGP ", BGP), ("OBP0", OBP0), ("OBP1", OBP1)] {
+        let palette = memory.peek(register);
+        let shades: Vec<String> = (0..4).map(|c| ((palette >> (c * 2)) & 0b11).to_string()).collect();
+        writeln!(text, "{name} ${palette:02X}  {}", shades.join(" ")).unwrap();
+    }
+    if let Some(cgb) = &memory.cgb {
+        for palette in 0..8 {
+            let bg: Vec<String> = (0..4).map(|c| format!("{:04X}", cgb.bg_colour(palette, c))).collect();
+            let obj: Vec<String> = (0..4).map(|c| format!("{:04X}", cgb.obj_colour(palette, c))).collect();
+            writeln!(text, "BG{palette}  {}   OBJ{palette}  {}", bg.join(" "), obj.join(" ")).unwrap();
+        }
+    }
+    text
+}
+
+/// writes every viewer into the directory, creating it if it has to
+pub fn dump(memory: &Memory, dir: &str, palette: Palette) -> Result<(), String> {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        return Err(format!("could not create {dir}: {e}"));
+    }
+    let images = [
+        ("tiles.png", tile_sheet(memory, palette)),
+        ("bg0.png", background_map(memory, 0)),
+        ("bg1.png", background_map(memory, 1)),
+        ("oam.png", oam_sheet(memory)),
+        ("palettes.png", palette_sheet(memory)),
+    ];
+    let path = |name: &str| std::path::Path::new(dir).join(name);
+    for (name, image) in images {
+        let data = png::encode(image.width, image.height, &image.rgba);
+        if let Err(e) = std::fs::write(path(name), data) {
+            return Err(format!("could not write {name}: {e}"));
+        }
+    }
+    let text = format!("{}\n{}", oam_table(memory), palette_table(memory));
+    if let Err(e) = std::fs::write(path("oam.txt"), text) {
+        return Err(format!("could not write oam.txt: {e}"));
+    }
+    Ok(())
+}